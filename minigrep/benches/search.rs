@@ -0,0 +1,196 @@
+// Compares `minigrep::search` with checking `line.contains` on every line,
+// and the streaming search the command line uses with running the matcher
+// on every line read, as it did before skipping ahead to the needle, over
+// synthetic corpora of a few kinds. Run with `cargo bench`; set
+// MINIGREP_BENCH_MB to change the size of each corpus.
+
+use std::env;
+use std::hint::black_box;
+use std::io::{self, BufRead, Cursor};
+use std::time::{Duration, Instant};
+
+use minigrep::context::{Context, ContextLine};
+use minigrep::matcher::Matcher;
+use minigrep::stream::StreamSearcher;
+
+const RUNS: usize = 5;
+
+const WORDS: &[&str] = &[
+    "the", "of", "and", "to", "in", "is", "that", "it", "was", "for", "on", "are", "with", "as",
+    "his", "they", "be", "at", "one", "have", "this", "from", "or", "had", "by", "word", "but",
+    "what", "some", "we", "can", "out", "other", "were", "all", "there", "when", "up", "use",
+    "your", "how", "said", "an", "each", "she", "which", "do", "their", "time", "if", "will",
+    "way", "about", "many", "then", "them", "write",
+];
+
+const IDENTIFIERS: &[&str] = &[
+    "let", "fn", "self", "match", "Some", "None", "Ok", "Err", "return", "impl", "pub", "usize",
+    "String", "Vec", "config", "matcher", "line", "path", "result", "buffer",
+];
+
+// A small xorshift generator, so every run searches the same text.
+struct Rng(u64);
+
+impl Rng {
+    fn below(&mut self, n: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % n as u64) as usize
+    }
+}
+
+// Lines of random words between `min` and `max` bytes long, with
+// `needle` put on roughly one line in `every`.
+fn corpus(
+    size: usize,
+    words: &[&str],
+    min: usize,
+    max: usize,
+    needle: &str,
+    every: usize,
+) -> String {
+    let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+    let mut text = String::with_capacity(size + max);
+
+    while text.len() < size {
+        let length = min + rng.below(max - min);
+        let start = text.len();
+        let with_needle = rng.below(every) == 0;
+        while text.len() - start < length {
+            if text.len() > start {
+                text.push(' ');
+            }
+            if with_needle && rng.below(8) == 0 {
+                text.push_str(needle);
+            } else {
+                text.push_str(words[rng.below(words.len())]);
+            }
+        }
+        text.push('\n');
+    }
+
+    text
+}
+
+fn naive<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+    contents
+        .lines()
+        .filter(|line| line.contains(query))
+        .collect()
+}
+
+// The line-by-line loop `StreamSearcher` used to run for every line.
+fn per_line(matcher: &Matcher, contents: &str) -> usize {
+    let mut reader = Cursor::new(contents);
+    let mut context = Context::new(0, 0);
+    let mut buffer = Vec::new();
+    let mut line_number = 0;
+    let mut byte_offset = 0;
+    let mut found = 0;
+
+    loop {
+        buffer.clear();
+        let read = reader.read_until(b'\n', &mut buffer).unwrap();
+        if read == 0 {
+            return found;
+        }
+        line_number += 1;
+        black_box(buffer.contains(&0));
+
+        let line = String::from_utf8_lossy(buffer.strip_suffix(b"\n").unwrap_or(&buffer));
+        let ranges = matcher.find_all(&line);
+        let selected = !ranges.is_empty();
+        if selected {
+            found += 1;
+        }
+        let mut emit = |line: ContextLine| {
+            black_box(line);
+            Ok::<_, io::Error>(())
+        };
+        context
+            .push(line_number, byte_offset, &line, selected, ranges, &mut emit)
+            .unwrap();
+        byte_offset += read;
+    }
+}
+
+fn stream(matcher: &Matcher, contents: &str) -> usize {
+    StreamSearcher::new(matcher)
+        .search(Cursor::new(contents), |line| {
+            black_box(line);
+            Ok(())
+        })
+        .unwrap()
+        .count
+}
+
+// The fastest of several runs, along with the number of lines found.
+fn time<F: Fn() -> usize>(search: F) -> (Duration, usize) {
+    (0..RUNS)
+        .map(|_| {
+            let started = Instant::now();
+            let found = black_box(search());
+            (started.elapsed(), found)
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    let megabytes: usize = env::var("MINIGREP_BENCH_MB")
+        .ok()
+        .and_then(|mb| mb.parse().ok())
+        .unwrap_or(32);
+    let size = megabytes << 20;
+
+    let corpora = [
+        ("prose", corpus(size, WORDS, 40, 100, "Holmes", 2000)),
+        ("long lines", corpus(size, WORDS, 1000, 4000, "Holmes", 200)),
+        (
+            "code",
+            corpus(size, IDENTIFIERS, 10, 80, "unwrap_or_else", 500),
+        ),
+    ];
+    let queries = ["Holmes", "unwrap_or_else", "the", "absent needle", "zq"];
+
+    println!(
+        "{:<12} {:<16} {:>8} {:>12} {:>12} {:>8} {:>12} {:>12} {:>8}",
+        "corpus",
+        "query",
+        "lines",
+        "contains",
+        "minigrep",
+        "speedup",
+        "per line",
+        "stream",
+        "speedup"
+    );
+    for (name, contents) in &corpora {
+        for query in queries {
+            let (naive_time, expected) = time(|| naive(query, contents).len());
+            let (search_time, found) = time(|| minigrep::search(query, contents).len());
+            assert_eq!(expected, found, "{} in {}", query, name);
+
+            let matcher = Matcher::literal(query);
+            let (line_time, found) = time(|| per_line(&matcher, contents));
+            assert_eq!(expected, found, "{} in {}", query, name);
+            let (stream_time, found) = time(|| stream(&matcher, contents));
+            assert_eq!(expected, found, "{} in {}", query, name);
+
+            let ms = |time: Duration| time.as_secs_f64() * 1000.0;
+            println!(
+                "{:<12} {:<16} {:>8} {:>10.2}ms {:>10.2}ms {:>7.1}x {:>10.2}ms {:>10.2}ms {:>7.1}x",
+                name,
+                format!("{:?}", query),
+                found,
+                ms(naive_time),
+                ms(search_time),
+                naive_time.as_secs_f64() / search_time.as_secs_f64(),
+                ms(line_time),
+                ms(stream_time),
+                line_time.as_secs_f64() / stream_time.as_secs_f64()
+            );
+        }
+    }
+}
@@ -0,0 +1,324 @@
+use std::collections::VecDeque;
+use std::ops::Range;
+
+use crate::case;
+
+// Aho-Corasick automaton for finding many literal patterns in a single
+// pass over the text. Patterns are stored in a trie whose nodes also keep
+// a failure link to the longest proper suffix that is itself a prefix of
+// some pattern, so the text is never re-scanned no matter how many
+// patterns there are.
+
+#[derive(Debug, Clone)]
+struct State {
+    // Sorted by character so lookups can use a binary search.
+    next: Vec<(char, usize)>,
+    fail: usize,
+    // Length in characters of the prefix this state represents.
+    depth: usize,
+    // Length of the longest pattern ending at this state, following
+    // failure links.
+    longest: Option<usize>,
+    // Whether the prefix this state represents is a pattern itself.
+    is_pattern: bool,
+}
+
+impl State {
+    fn new(depth: usize) -> State {
+        State {
+            next: Vec::new(),
+            fail: 0,
+            depth,
+            longest: None,
+            is_pattern: false,
+        }
+    }
+
+    fn lookup(&self, c: char) -> Option<usize> {
+        self.next
+            .binary_search_by_key(&c, |&(c, _)| c)
+            .ok()
+            .map(|i| self.next[i].1)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AhoCorasick {
+    states: Vec<State>,
+    case_insensitive: bool,
+}
+
+impl AhoCorasick {
+    pub fn new<I, S>(patterns: I, case_insensitive: bool) -> AhoCorasick
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut states = vec![State::new(0)];
+
+        for pattern in patterns {
+            let pattern = pattern.as_ref();
+            let chars = if case_insensitive {
+                case::fold_str(pattern)
+            } else {
+                pattern.chars().collect()
+            };
+
+            let mut current = 0;
+            for c in chars {
+                current = match states[current].lookup(c) {
+                    Some(next) => next,
+                    None => {
+                        let next = states.len();
+                        states.push(State::new(states[current].depth + 1));
+                        let transitions = &mut states[current].next;
+                        let at = transitions.partition_point(|&(t, _)| t < c);
+                        transitions.insert(at, (c, next));
+                        next
+                    }
+                };
+            }
+            states[current].longest = Some(states[current].depth);
+            states[current].is_pattern = true;
+        }
+
+        let mut automaton = AhoCorasick {
+            states,
+            case_insensitive,
+        };
+        automaton.link_failures();
+        automaton
+    }
+
+    // Breadth first, so the failure target of every state is complete
+    // before its children are visited.
+    fn link_failures(&mut self) {
+        let mut queue = VecDeque::new();
+        queue.push_back(0);
+
+        while let Some(state) = queue.pop_front() {
+            for i in 0..self.states[state].next.len() {
+                let (c, child) = self.states[state].next[i];
+                let fail = if state == 0 {
+                    0
+                } else {
+                    self.step(self.states[state].fail, c)
+                };
+
+                let inherited = self.states[fail].longest;
+                let child_state = &mut self.states[child];
+                child_state.fail = fail;
+                child_state.longest = child_state.longest.max(inherited);
+                queue.push_back(child);
+            }
+        }
+    }
+
+    fn step(&self, mut state: usize, c: char) -> usize {
+        loop {
+            if let Some(next) = self.states[state].lookup(c) {
+                return next;
+            }
+            if state == 0 {
+                return 0;
+            }
+            state = self.states[state].fail;
+        }
+    }
+
+    /// Finds the leftmost occurrence of any pattern starting at or after
+    /// byte `start`, preferring the longest pattern among those starting
+    /// at the same position.
+    pub fn find_at(&self, text: &str, start: usize) -> Option<Range<usize>> {
+        // For each character fed to the automaton so far, the bytes of the
+        // text it came from. Folding can turn one character into several.
+        let mut spans: Vec<Range<usize>> = Vec::new();
+        let mut best: Option<(usize, usize)> = self.states[0].longest.map(|_| (0, 0));
+        let mut state = 0;
+
+        'text: for (i, c) in text[start..].char_indices() {
+            let span = start + i..start + i + c.len_utf8();
+            let mut exact = std::iter::once(c);
+            let mut folded;
+            let chars: &mut dyn Iterator<Item = char> = if self.case_insensitive {
+                folded = case::fold(c);
+                &mut folded
+            } else {
+                &mut exact
+            };
+
+            for c in chars {
+                spans.push(span.clone());
+                state = self.step(state, c);
+            }
+            let read = spans.len();
+
+            // Matches are only taken once the whole of a character has been
+            // fed, so none ends partway through what it folds to.
+            if let Some(length) = self.longest_whole(state, &spans) {
+                let first = read - length;
+                let better = match best {
+                    None => true,
+                    Some((best_first, best_length)) => {
+                        first < best_first || (first == best_first && length > best_length)
+                    }
+                };
+                if better {
+                    best = Some((first, length));
+                }
+            }
+
+            // Nothing that is still being matched can start early enough to
+            // beat the best match found so far.
+            if let Some((best_first, _)) = best {
+                if read - self.states[state].depth > best_first {
+                    break 'text;
+                }
+            }
+        }
+
+        best.map(|(first, length)| {
+            if length == 0 {
+                let at = spans.get(first).map_or(text.len(), |span| span.start);
+                return at..at;
+            }
+            spans[first].start..spans[first + length - 1].end
+        })
+    }
+
+    // The length of the longest pattern ending at `state` that also starts
+    // where a character of the text does, given the character each folded
+    // character fed so far came from. A pattern can only start partway
+    // through a character that folds to several, like `s` in `ß`.
+    fn longest_whole(&self, state: usize, spans: &[Range<usize>]) -> Option<usize> {
+        let read = spans.len();
+        let starts_whole = |length: usize| {
+            let first = read - length;
+            first == 0 || first == read || spans[first - 1] != spans[first]
+        };
+
+        let longest = self.states[state].longest?;
+        if starts_whole(longest) {
+            return Some(longest);
+        }
+        // Shorter patterns ending here are found through the failure links.
+        let mut state = state;
+        loop {
+            let current = &self.states[state];
+            if current.is_pattern && starts_whole(current.depth) {
+                return Some(current.depth);
+            }
+            if state == 0 {
+                return None;
+            }
+            state = current.fail;
+        }
+    }
+
+    /// Every occurrence of a pattern that starts at byte `at`, longest
+    /// first, for when the one `find_at` prefers will not do.
+    pub fn matches_at(&self, text: &str, at: usize) -> Vec<Range<usize>> {
+        let mut found = Vec::new();
+        let mut state = 0;
+        if self.states[state].is_pattern {
+            found.push(at..at);
+        }
+
+        'text: for (i, c) in text[at..].char_indices() {
+            let mut exact = std::iter::once(c);
+            let mut folded;
+            let chars: &mut dyn Iterator<Item = char> = if self.case_insensitive {
+                folded = case::fold(c);
+                &mut folded
+            } else {
+                &mut exact
+            };
+
+            for c in chars {
+                state = match self.states[state].lookup(c) {
+                    Some(next) => next,
+                    None => break 'text,
+                };
+            }
+            // Only once the whole of a character has been fed, so a match
+            // never ends partway through one.
+            if self.states[state].is_pattern {
+                found.push(at..at + i + c.len_utf8());
+            }
+        }
+
+        found.reverse();
+        found
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.find_at(text, 0).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(patterns: &[&str], text: &str) -> Option<Range<usize>> {
+        AhoCorasick::new(patterns, false).find_at(text, 0)
+    }
+
+    #[test]
+    fn finds_leftmost_pattern() {
+        assert_eq!(Some(1..4), find(&["she", "he", "hers"], "ushers"));
+        assert_eq!(Some(1..3), find(&["bcd", "ab", "bc"], "xabcd"));
+        assert_eq!(None, find(&["foo", "bar"], "baz"));
+    }
+
+    #[test]
+    fn prefers_longest_at_same_start() {
+        assert_eq!(Some(0..4), find(&["he", "hers", "her"], "hers"));
+    }
+
+    #[test]
+    fn finds_every_pattern_at_a_start() {
+        let automaton = AhoCorasick::new(["a", "a-b", "a-bc", "b"], false);
+        assert_eq!(vec![0..4, 0..3, 0..1], automaton.matches_at("a-bcd", 0));
+        assert_eq!(vec![2..3], automaton.matches_at("a-bcd", 2));
+        assert!(automaton.matches_at("a-bcd", 3).is_empty());
+    }
+
+    #[test]
+    fn follows_failure_links() {
+        assert_eq!(Some(2..5), find(&["abcx", "cde"], "abcde"));
+        assert_eq!(Some(4..8), find(&["aaab"], "aaaaaaab"));
+    }
+
+    #[test]
+    fn case_insensitive_and_unicode() {
+        let automaton = AhoCorasick::new(["token", "ÉTÉ"], true);
+        assert_eq!(Some(4..9), automaton.find_at("the TOKEN", 0));
+        assert_eq!(Some(2..7), automaton.find_at("l'été", 0));
+
+        let automaton = AhoCorasick::new(["strasse", "σοφος"], true);
+        assert_eq!(Some(0..7), automaton.find_at("STRAßE", 0));
+        assert_eq!(Some(0..10), automaton.find_at("ΣΟΦΟΣ", 0));
+    }
+
+    #[test]
+    fn matches_whole_folded_characters() {
+        let automaton = AhoCorasick::new(["s"], true);
+        assert_eq!(None, automaton.find_at("ß", 0));
+        assert_eq!(Some(2..3), automaton.find_at("ßs", 0));
+
+        let automaton = AhoCorasick::new(["sx", "x"], true);
+        assert_eq!(Some(2..3), automaton.find_at("ßx", 0));
+        let automaton = AhoCorasick::new(["ss", "x"], true);
+        assert_eq!(Some(1..3), automaton.find_at("xß", 1));
+    }
+
+    #[test]
+    fn many_patterns() {
+        let patterns: Vec<String> = (0..5000).map(|n| format!("secret-{}-", n)).collect();
+        let automaton = AhoCorasick::new(&patterns, false);
+
+        assert_eq!(Some(6..18), automaton.find_at("found secret-4321- here", 0));
+        assert_eq!(None, automaton.find_at("found secret-99999 here", 0));
+    }
+}
@@ -0,0 +1,133 @@
+use std::ops::Range;
+
+// Unicode case folding for case-insensitive matching. The standard library
+// has no folding tables, but lowering, raising and lowering again gives
+// the full folding of every character except the Turkic dotless i, which
+// has no folding at all:
+//
+//   ß -> ss   ẞ -> ss   ς -> σ   ﬁ -> fi   İ -> i̇   K (Kelvin) -> k
+
+/// The characters `c` folds to. Most characters fold to a single one, but
+/// some expand, like `ß` to `ss`.
+pub fn fold(c: char) -> impl Iterator<Item = char> {
+    let dotless = c == 'ı';
+
+    Some(c)
+        .filter(|_| !dotless)
+        .into_iter()
+        .flat_map(char::to_lowercase)
+        .flat_map(char::to_uppercase)
+        .flat_map(char::to_lowercase)
+        .chain(Some(c).filter(|_| dotless))
+}
+
+/// The simple folding of `c`: its full folding when that is a single
+/// character, otherwise `c` itself, lowercased when possible.
+pub fn fold_simple(c: char) -> char {
+    let mut folded = fold(c);
+    match (folded.next(), folded.next()) {
+        (Some(f), None) => f,
+        _ => {
+            let mut lower = c.to_lowercase();
+            match (lower.next(), lower.next()) {
+                (Some(l), None) => l,
+                _ => c,
+            }
+        }
+    }
+}
+
+/// Folds every character of `s`.
+pub fn fold_str(s: &str) -> Vec<char> {
+    s.chars().flat_map(fold).collect()
+}
+
+/// Finds the first place at or after byte `start` where the folding of
+/// `line` equals `query`, which must already be folded. The returned range
+/// always falls on character boundaries of the original line; a match may
+/// not end halfway through the expansion of a character.
+pub fn find(line: &str, query: &[char], start: usize) -> Option<Range<usize>> {
+    if query.is_empty() {
+        return Some(start..start);
+    }
+
+    for (i, _) in line[start..].char_indices() {
+        let begin = start + i;
+        let mut wanted = query.iter();
+        let mut end = begin;
+
+        for c in line[begin..].chars() {
+            let complete = fold(c).all(|f| wanted.next() == Some(&f));
+            if !complete {
+                break;
+            }
+            end += c.len_utf8();
+            if wanted.len() == 0 {
+                return Some(begin..end);
+            }
+        }
+    }
+
+    None
+}
+
+/// Whether `pattern` has an uppercase letter, for `--smart-case`. In a
+/// regular expression the character after a backslash is not a literal,
+/// so `\W` and `\S` do not count.
+pub fn has_uppercase(pattern: &str, regex: bool) -> bool {
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if regex && c == '\\' {
+            chars.next();
+        } else if c.is_uppercase() {
+            return true;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn folded(s: &str) -> String {
+        fold_str(s).into_iter().collect()
+    }
+
+    #[test]
+    fn full_folding() {
+        assert_eq!("strasse", folded("Straße"));
+        assert_eq!("strasse", folded("STRAẞE"));
+        assert_eq!("σοφοσ", folded("ΣΟΦΟΣ"));
+        assert_eq!("σοφοσ", folded("σοφος"));
+        assert_eq!("file", folded("ﬁle"));
+        assert_eq!("k", folded("\u{212a}"));
+        assert_eq!("ı", folded("ı"));
+    }
+
+    #[test]
+    fn simple_folding() {
+        assert_eq!('σ', fold_simple('ς'));
+        assert_eq!('ß', fold_simple('ß'));
+        assert_eq!('ß', fold_simple('ẞ'));
+        assert_eq!('a', fold_simple('A'));
+    }
+
+    #[test]
+    fn finds_across_expansions() {
+        let query = fold_str("SS");
+        assert_eq!(Some(2..4), find("Maß", &query, 0));
+        assert_eq!(Some(0..2), find("ss", &fold_str("ß"), 0));
+        // Half of an expanded character is not a match.
+        assert_eq!(None, find("ß", &fold_str("s"), 0));
+        assert_eq!(Some(3..5), find("ab ΣΑ", &fold_str("ς"), 0));
+    }
+
+    #[test]
+    fn uppercase_detection() {
+        assert!(has_uppercase("Rust", false));
+        assert!(!has_uppercase("rust", false));
+        assert!(!has_uppercase(r"\w+\S", true));
+        assert!(has_uppercase(r"\S", false));
+    }
+}
@@ -0,0 +1,184 @@
+use std::env;
+use std::io::{self, IsTerminal};
+use std::str::FromStr;
+
+// ANSI colors for highlighting output. Each kind of output has its own
+// style, which can be changed with specs in the same format as ripgrep's
+// `--colors`:
+//
+//   path:fg:blue   line:fg:yellow   match:style:nobold   separator:none
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl FromStr for ColorChoice {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<ColorChoice, &'static str> {
+        match s {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => Err("invalid color choice, expected auto, always or never"),
+        }
+    }
+}
+
+impl ColorChoice {
+    /// Whether to color output. `Auto` colors only when standard output is
+    /// a terminal and `NO_COLOR` is not set.
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                let no_color = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+                !no_color && io::stdout().is_terminal()
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Color {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+}
+
+impl FromStr for Color {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Color, &'static str> {
+        match s {
+            "black" => Ok(Color::Black),
+            "red" => Ok(Color::Red),
+            "green" => Ok(Color::Green),
+            "yellow" => Ok(Color::Yellow),
+            "blue" => Ok(Color::Blue),
+            "magenta" => Ok(Color::Magenta),
+            "cyan" => Ok(Color::Cyan),
+            "white" => Ok(Color::White),
+            _ => Err("unknown color name"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Style {
+    pub color: Option<Color>,
+    pub bold: bool,
+}
+
+impl Style {
+    fn new(color: Color, bold: bool) -> Style {
+        Style {
+            color: Some(color),
+            bold,
+        }
+    }
+
+    /// Wraps `text` in the escape sequences for this style.
+    pub fn paint(&self, text: &str) -> String {
+        let mut codes = Vec::new();
+        if self.bold {
+            codes.push("1".to_string());
+        }
+        if let Some(color) = self.color {
+            codes.push((30 + color as u8).to_string());
+        }
+
+        if codes.is_empty() {
+            text.to_string()
+        } else {
+            format!("\x1b[{}m{}\x1b[0m", codes.join(";"), text)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Colors {
+    pub path: Style,
+    pub line_number: Style,
+    pub matched: Style,
+    pub separator: Style,
+}
+
+impl Default for Colors {
+    // The same defaults as GNU grep.
+    fn default() -> Colors {
+        Colors {
+            path: Style::new(Color::Magenta, false),
+            line_number: Style::new(Color::Green, false),
+            matched: Style::new(Color::Red, true),
+            separator: Style::new(Color::Cyan, false),
+        }
+    }
+}
+
+impl Colors {
+    /// Applies a spec such as `match:fg:blue`, `path:style:bold` or
+    /// `line:none`.
+    pub fn set(&mut self, spec: &str) -> Result<(), &'static str> {
+        let parts: Vec<&str> = spec.split(':').collect();
+
+        let style = match parts[0] {
+            "path" => &mut self.path,
+            "line" => &mut self.line_number,
+            "match" => &mut self.matched,
+            "separator" => &mut self.separator,
+            _ => return Err("unknown color spec type, expected path, line, match or separator"),
+        };
+
+        match parts[1..] {
+            ["none"] => *style = Style::default(),
+            ["fg", color] => style.color = Some(color.parse()?),
+            ["style", "bold"] => style.bold = true,
+            ["style", "nobold"] => style.bold = false,
+            _ => return Err("invalid color spec"),
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paints_styles() {
+        let colors = Colors::default();
+        assert_eq!("\x1b[1;31mfoo\x1b[0m", colors.matched.paint("foo"));
+        assert_eq!("\x1b[35mpath\x1b[0m", colors.path.paint("path"));
+        assert_eq!("plain", Style::default().paint("plain"));
+    }
+
+    #[test]
+    fn applies_specs() {
+        let mut colors = Colors::default();
+        colors.set("match:fg:blue").unwrap();
+        colors.set("match:style:nobold").unwrap();
+        colors.set("line:none").unwrap();
+
+        assert_eq!(Style::new(Color::Blue, false), colors.matched);
+        assert_eq!(Style::default(), colors.line_number);
+        assert!(colors.set("match:fg:purple").is_err());
+        assert!(colors.set("title:fg:red").is_err());
+    }
+
+    #[test]
+    fn explicit_choices_ignore_the_terminal() {
+        assert!(ColorChoice::Always.enabled());
+        assert!(!ColorChoice::Never.enabled());
+    }
+}
@@ -0,0 +1,1007 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::color::{ColorChoice, Colors};
+use crate::expr::Expr;
+use crate::walk::Filter;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputMode {
+    /// Print every selected line.
+    Lines,
+    /// Print the number of selected lines per file.
+    Count,
+    /// Print the path of each file with at least one selected line.
+    FilesWithMatches,
+    /// Print the path of each file without any selected line.
+    FilesWithoutMatch,
+    /// Print nothing; only the exit status tells whether anything matched.
+    Quiet,
+}
+
+pub struct Config {
+    pub query: String,
+    pub patterns: Vec<String>,
+    pub pattern_files: Vec<String>,
+    /// A boolean expression over patterns, from `--query`, selecting lines
+    /// instead of a single pattern.
+    pub expression: Option<Expr<String>>,
+    pub paths: Vec<String>,
+    /// Which files to search in directories, from `--glob` and `--type`.
+    pub filter: Filter,
+    pub case_sensitive: bool,
+    /// Overrides `case_sensitive`, ignoring case unless a pattern has an
+    /// uppercase letter.
+    pub smart_case: bool,
+    pub regex: bool,
+    /// Allow this many edits between the query and what it matches.
+    pub fuzzy: Option<usize>,
+    pub word_regexp: bool,
+    pub line_regexp: bool,
+    /// Let matches span lines, printing every line a match touches.
+    pub multiline: bool,
+    pub before_context: usize,
+    pub after_context: usize,
+    pub line_number: bool,
+    pub byte_offset: bool,
+    pub threads: usize,
+    pub sort_by_path: bool,
+    /// Skip files that a trigram index rules out, see `index`.
+    pub index: bool,
+    pub invert_match: bool,
+    pub text: bool,
+    pub max_count: Option<usize>,
+    pub output: OutputMode,
+    pub color: ColorChoice,
+    pub colors: Colors,
+    pub json: bool,
+    /// Rewrite matches with this, expanding `$1`-style group references.
+    pub replace: Option<String>,
+    /// Print the replacements as a diff instead of applying them.
+    pub dry_run: bool,
+    /// Apply the replacements to the files searched.
+    pub in_place: bool,
+    /// Keep reading files as they grow, like `tail -f`.
+    pub follow: bool,
+    /// Search one file as the query is typed, see `interactive`.
+    pub interactive: bool,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            query: String::new(),
+            patterns: Vec::new(),
+            pattern_files: Vec::new(),
+            expression: None,
+            paths: Vec::new(),
+            filter: Filter::default(),
+            case_sensitive: true,
+            smart_case: false,
+            regex: false,
+            fuzzy: None,
+            word_regexp: false,
+            line_regexp: false,
+            multiline: false,
+            before_context: 0,
+            after_context: 0,
+            line_number: false,
+            byte_offset: false,
+            threads: 0,
+            sort_by_path: false,
+            index: false,
+            invert_match: false,
+            text: false,
+            max_count: None,
+            output: OutputMode::Lines,
+            color: ColorChoice::Auto,
+            colors: Colors::default(),
+            json: false,
+            replace: None,
+            dry_run: false,
+            in_place: false,
+            follow: false,
+            interactive: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    /// `--help` was given; displays as the usage text.
+    Help,
+    /// `--version` was given; displays as the version string.
+    Version,
+    UnknownOption(String),
+    MissingValue(String),
+    UnexpectedValue(String),
+    InvalidValue {
+        option: String,
+        value: String,
+        reason: String,
+    },
+    MissingPattern,
+    /// An option that only makes sense along with another one.
+    Requires {
+        option: String,
+        required: String,
+    },
+    Conflict {
+        option: String,
+        other: String,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Help => write!(f, "{}", usage()),
+            ConfigError::Version => write!(f, "minigrep {}", env!("CARGO_PKG_VERSION")),
+            ConfigError::UnknownOption(option) => write!(f, "unknown option '{}'", option),
+            ConfigError::MissingValue(option) => {
+                write!(f, "option '{}' requires a value", option)
+            }
+            ConfigError::UnexpectedValue(option) => {
+                write!(f, "option '{}' does not take a value", option)
+            }
+            ConfigError::InvalidValue {
+                option,
+                value,
+                reason,
+            } => write!(f, "invalid value '{}' for '{}': {}", value, option, reason),
+            ConfigError::MissingPattern => write!(f, "no pattern given"),
+            ConfigError::Requires { option, required } => {
+                write!(f, "option '{}' requires '{}'", option, required)
+            }
+            ConfigError::Conflict { option, other } => {
+                write!(f, "option '{}' cannot be used with {}", option, other)
+            }
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+struct Opt {
+    short: Option<char>,
+    long: &'static str,
+    value: Option<&'static str>,
+    help: &'static str,
+}
+
+const OPTIONS: &[Opt] = &[
+    Opt {
+        short: Some('e'),
+        long: "regexp",
+        value: Some("PATTERN"),
+        help: "Search for PATTERN; may be given more than once",
+    },
+    Opt {
+        short: Some('f'),
+        long: "file",
+        value: Some("FILE"),
+        help: "Read patterns from FILE, one per line",
+    },
+    Opt {
+        short: None,
+        long: "query",
+        value: Some("EXPR"),
+        help: "Select lines matching EXPR, e.g. 'a AND NOT (b OR c)'",
+    },
+    Opt {
+        short: Some('E'),
+        long: "regex",
+        value: None,
+        help: "Treat patterns as regular expressions",
+    },
+    Opt {
+        short: None,
+        long: "fuzzy",
+        value: Some("K"),
+        help: "Match text within K edits of the query",
+    },
+    Opt {
+        short: Some('g'),
+        long: "glob",
+        value: Some("GLOB"),
+        help: "Search only files matching GLOB in directories; !GLOB excludes",
+    },
+    Opt {
+        short: Some('t'),
+        long: "type",
+        value: Some("TYPE"),
+        help: "Search only files of TYPE in directories, e.g. rust or toml",
+    },
+    Opt {
+        short: Some('i'),
+        long: "ignore-case",
+        value: None,
+        help: "Match case insensitively",
+    },
+    Opt {
+        short: Some('s'),
+        long: "case-sensitive",
+        value: None,
+        help: "Match case sensitively",
+    },
+    Opt {
+        short: Some('S'),
+        long: "smart-case",
+        value: None,
+        help: "Ignore case unless a pattern has an uppercase letter",
+    },
+    Opt {
+        short: Some('w'),
+        long: "word-regexp",
+        value: None,
+        help: "Select only matches that form whole words",
+    },
+    Opt {
+        short: Some('x'),
+        long: "line-regexp",
+        value: None,
+        help: "Select only matches that span the whole line",
+    },
+    Opt {
+        short: Some('U'),
+        long: "multiline",
+        value: None,
+        help: "Let matches span lines; print every line a match touches",
+    },
+    Opt {
+        short: Some('v'),
+        long: "invert-match",
+        value: None,
+        help: "Select lines that do not match",
+    },
+    Opt {
+        short: Some('a'),
+        long: "text",
+        value: None,
+        help: "Search binary files as if they were text",
+    },
+    Opt {
+        short: Some('m'),
+        long: "max-count",
+        value: Some("NUM"),
+        help: "Stop reading a file after NUM selected lines",
+    },
+    Opt {
+        short: Some('n'),
+        long: "line-number",
+        value: None,
+        help: "Prefix each line with its line number",
+    },
+    Opt {
+        short: Some('b'),
+        long: "byte-offset",
+        value: None,
+        help: "Prefix each line with its byte offset",
+    },
+    Opt {
+        short: Some('A'),
+        long: "after-context",
+        value: Some("NUM"),
+        help: "Print NUM lines after each match",
+    },
+    Opt {
+        short: Some('B'),
+        long: "before-context",
+        value: Some("NUM"),
+        help: "Print NUM lines before each match",
+    },
+    Opt {
+        short: Some('C'),
+        long: "context",
+        value: Some("NUM"),
+        help: "Print NUM lines before and after each match",
+    },
+    Opt {
+        short: Some('c'),
+        long: "count",
+        value: None,
+        help: "Print only the number of selected lines per file",
+    },
+    Opt {
+        short: Some('l'),
+        long: "files-with-matches",
+        value: None,
+        help: "Print only the paths of files with matches",
+    },
+    Opt {
+        short: Some('L'),
+        long: "files-without-match",
+        value: None,
+        help: "Print only the paths of files without matches",
+    },
+    Opt {
+        short: Some('q'),
+        long: "quiet",
+        value: None,
+        help: "Print nothing; exit with 0 if anything matched",
+    },
+    Opt {
+        short: Some('r'),
+        long: "replace",
+        value: Some("TEXT"),
+        help: "Print matches replaced with TEXT, which may use $1 or ${1}",
+    },
+    Opt {
+        short: None,
+        long: "dry-run",
+        value: None,
+        help: "With --replace, print a diff of the changes",
+    },
+    Opt {
+        short: None,
+        long: "in-place",
+        value: None,
+        help: "With --replace, rewrite the files",
+    },
+    Opt {
+        short: None,
+        long: "follow",
+        value: None,
+        help: "Keep reading files as they grow, like tail -f",
+    },
+    Opt {
+        short: None,
+        long: "interactive",
+        value: None,
+        help: "Search FILE as the query is typed, full screen",
+    },
+    Opt {
+        short: None,
+        long: "color",
+        value: Some("WHEN"),
+        help: "Color output: auto, always or never",
+    },
+    Opt {
+        short: None,
+        long: "colors",
+        value: Some("SPEC"),
+        help: "Set a color, e.g. match:fg:blue or path:none",
+    },
+    Opt {
+        short: None,
+        long: "json",
+        value: None,
+        help: "Print results as JSON Lines",
+    },
+    Opt {
+        short: Some('j'),
+        long: "threads",
+        value: Some("NUM"),
+        help: "Search NUM files in parallel",
+    },
+    Opt {
+        short: None,
+        long: "sort",
+        value: Some("KEY"),
+        help: "Print results in a stable order; KEY must be 'path'",
+    },
+    Opt {
+        short: None,
+        long: "index",
+        value: None,
+        help: "Use and update the index of directories searched",
+    },
+    Opt {
+        short: Some('h'),
+        long: "help",
+        value: None,
+        help: "Print this help and exit",
+    },
+    Opt {
+        short: Some('V'),
+        long: "version",
+        value: None,
+        help: "Print the version and exit",
+    },
+];
+
+/// The text printed by `--help`.
+pub fn usage() -> String {
+    let mut text = String::from(
+        "Usage: minigrep [OPTIONS] PATTERN [PATH...]\n       \
+         minigrep [OPTIONS] -e PATTERN... [PATH...]\n       \
+         minigrep [OPTIONS] --interactive FILE\n       \
+         minigrep index build [DIR]\n\n\
+         Searches standard input when no PATH is given or PATH is -.\n\
+         index build writes a trigram index of DIR, or the current directory,\n\
+         for --index to use.\n\nOptions:",
+    );
+
+    for opt in OPTIONS {
+        let short = match opt.short {
+            Some(short) => format!("-{}, ", short),
+            None => String::from("    "),
+        };
+        let value = opt.value.map(|v| format!(" {}", v)).unwrap_or_default();
+        let flag = format!("{}--{}{}", short, opt.long, value);
+        text.push_str(&format!("\n  {:<28} {}", flag, opt.help));
+    }
+
+    text
+}
+
+impl Config {
+    pub fn new(args: &[String]) -> Result<Config, ConfigError> {
+        let mut config = Config::default();
+        let mut positional = Vec::new();
+        let mut args = args.iter().skip(1);
+
+        while let Some(arg) = args.next() {
+            if arg == "--" {
+                positional.extend(args.by_ref().cloned());
+                break;
+            }
+
+            if let Some(long) = arg.strip_prefix("--") {
+                let (name, value) = match long.find('=') {
+                    Some(i) => (&long[..i], Some(long[i + 1..].to_string())),
+                    None => (long, None),
+                };
+                let opt = OPTIONS
+                    .iter()
+                    .find(|opt| opt.long == name)
+                    .ok_or_else(|| ConfigError::UnknownOption(format!("--{}", name)))?;
+                let option = format!("--{}", name);
+
+                let value = match (opt.value, value) {
+                    (None, Some(_)) => return Err(ConfigError::UnexpectedValue(option)),
+                    (None, None) => None,
+                    (Some(_), Some(value)) => Some(value),
+                    (Some(_), None) => Some(
+                        args.next()
+                            .cloned()
+                            .ok_or_else(|| ConfigError::MissingValue(option.clone()))?,
+                    ),
+                };
+                config.apply(opt, &option, value)?;
+            } else if arg.len() > 1 && arg.starts_with('-') {
+                // Short options can be grouped, as in `-nv`, and take their
+                // value from the rest of the group or the next argument,
+                // as in `-A3` or `-A 3`.
+                let group = &arg[1..];
+                for (i, short) in group.char_indices() {
+                    let opt = OPTIONS
+                        .iter()
+                        .find(|opt| opt.short == Some(short))
+                        .ok_or_else(|| ConfigError::UnknownOption(format!("-{}", short)))?;
+                    let option = format!("-{}", short);
+
+                    if opt.value.is_none() {
+                        config.apply(opt, &option, None)?;
+                        continue;
+                    }
+
+                    let rest = &group[i + short.len_utf8()..];
+                    let value = if rest.is_empty() {
+                        args.next()
+                            .cloned()
+                            .ok_or_else(|| ConfigError::MissingValue(option.clone()))?
+                    } else {
+                        rest.to_string()
+                    };
+                    config.apply(opt, &option, Some(value))?;
+                    break;
+                }
+            } else {
+                positional.push(arg.clone());
+            }
+        }
+
+        let mut positional = positional.into_iter();
+        if config.patterns.is_empty()
+            && config.pattern_files.is_empty()
+            && config.expression.is_none()
+            && !config.interactive
+        {
+            config.query = positional.next().ok_or(ConfigError::MissingPattern)?;
+        }
+        config.paths = positional.collect();
+        if config.paths.is_empty() {
+            config.paths.push(String::from(crate::STDIN_PATH));
+        }
+        config.check_replace()?;
+        config.check_fuzzy()?;
+        config.check_query()?;
+        config.check_follow()?;
+        config.check_interactive()?;
+
+        Ok(config)
+    }
+
+    fn check_replace(&self) -> Result<(), ConfigError> {
+        let requires = |option: &str| ConfigError::Requires {
+            option: option.to_string(),
+            required: String::from("--replace"),
+        };
+
+        if self.replace.is_none() && self.dry_run {
+            Err(requires("--dry-run"))
+        } else if self.replace.is_none() && self.in_place {
+            Err(requires("--in-place"))
+        } else if self.dry_run && self.in_place {
+            Err(ConfigError::Conflict {
+                option: String::from("--in-place"),
+                other: String::from("'--dry-run'"),
+            })
+        } else if self.in_place && self.paths.iter().any(|path| path == crate::STDIN_PATH) {
+            Err(ConfigError::Conflict {
+                option: String::from("--in-place"),
+                other: String::from("standard input"),
+            })
+        } else if self.replace.is_some() && self.multiline {
+            // Replacements are made a line at a time.
+            Err(ConfigError::Conflict {
+                option: String::from("--replace"),
+                other: String::from("'--multiline'"),
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    // Fuzzy matching works on a single plain query.
+    fn check_fuzzy(&self) -> Result<(), ConfigError> {
+        let conflict = |other: &str| ConfigError::Conflict {
+            option: String::from("--fuzzy"),
+            other: format!("'{}'", other),
+        };
+
+        if self.fuzzy.is_none() {
+            Ok(())
+        } else if self.regex {
+            Err(conflict("--regex"))
+        } else if !self.patterns.is_empty() {
+            Err(conflict("--regexp"))
+        } else if !self.pattern_files.is_empty() {
+            Err(conflict("--file"))
+        } else {
+            Ok(())
+        }
+    }
+
+    // An expression takes the place of every other way to give patterns,
+    // and is evaluated a line at a time.
+    fn check_query(&self) -> Result<(), ConfigError> {
+        let conflict = |other: &str| ConfigError::Conflict {
+            option: String::from("--query"),
+            other: format!("'{}'", other),
+        };
+
+        if self.expression.is_none() {
+            Ok(())
+        } else if !self.patterns.is_empty() {
+            Err(conflict("--regexp"))
+        } else if !self.pattern_files.is_empty() {
+            Err(conflict("--file"))
+        } else if self.fuzzy.is_some() {
+            Err(conflict("--fuzzy"))
+        } else if self.multiline {
+            Err(conflict("--multiline"))
+        } else {
+            Ok(())
+        }
+    }
+
+    // Following never reaches the end of a file, so only modes that print
+    // lines as they are found make sense.
+    fn check_follow(&self) -> Result<(), ConfigError> {
+        let conflict = |other: &str| ConfigError::Conflict {
+            option: String::from("--follow"),
+            other: other.to_string(),
+        };
+
+        if !self.follow {
+            return Ok(());
+        }
+        match self.output {
+            OutputMode::Lines => {}
+            OutputMode::Count => return Err(conflict("'--count'")),
+            OutputMode::FilesWithMatches => return Err(conflict("'--files-with-matches'")),
+            OutputMode::FilesWithoutMatch => return Err(conflict("'--files-without-match'")),
+            OutputMode::Quiet => return Err(conflict("'--quiet'")),
+        }
+        if self.multiline {
+            Err(conflict("'--multiline'"))
+        } else if self.dry_run {
+            Err(conflict("'--dry-run'"))
+        } else if self.in_place {
+            Err(conflict("'--in-place'"))
+        } else if self.paths.iter().any(|path| path == crate::STDIN_PATH) {
+            Err(conflict("standard input"))
+        } else {
+            Ok(())
+        }
+    }
+
+    // The query is typed rather than given, and results go to the terminal
+    // rather than to standard output.
+    fn check_interactive(&self) -> Result<(), ConfigError> {
+        let conflict = |other: &str| ConfigError::Conflict {
+            option: String::from("--interactive"),
+            other: other.to_string(),
+        };
+
+        if !self.interactive {
+            return Ok(());
+        }
+        match self.output {
+            OutputMode::Lines => {}
+            OutputMode::Count => return Err(conflict("'--count'")),
+            OutputMode::FilesWithMatches => return Err(conflict("'--files-with-matches'")),
+            OutputMode::FilesWithoutMatch => return Err(conflict("'--files-without-match'")),
+            OutputMode::Quiet => return Err(conflict("'--quiet'")),
+        }
+        if !self.patterns.is_empty() {
+            Err(conflict("'--regexp'"))
+        } else if !self.pattern_files.is_empty() {
+            Err(conflict("'--file'"))
+        } else if self.expression.is_some() {
+            Err(conflict("'--query'"))
+        } else if self.fuzzy.is_some() {
+            Err(conflict("'--fuzzy'"))
+        } else if self.invert_match {
+            Err(conflict("'--invert-match'"))
+        } else if self.multiline {
+            Err(conflict("'--multiline'"))
+        } else if self.json {
+            Err(conflict("'--json'"))
+        } else if self.replace.is_some() {
+            Err(conflict("'--replace'"))
+        } else if self.follow {
+            Err(conflict("'--follow'"))
+        } else if self.paths.iter().any(|path| path == crate::STDIN_PATH) {
+            Err(conflict("standard input"))
+        } else if self.paths.len() > 1 {
+            Err(conflict("more than one FILE"))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn apply(&mut self, opt: &Opt, option: &str, value: Option<String>) -> Result<(), ConfigError> {
+        let value = value.unwrap_or_default();
+        let invalid = |reason: &str| ConfigError::InvalidValue {
+            option: option.to_string(),
+            value: value.clone(),
+            reason: reason.to_string(),
+        };
+        let number = || -> Result<usize, ConfigError> {
+            value
+                .parse()
+                .map_err(|_| invalid("expected a non-negative integer"))
+        };
+
+        match opt.long {
+            "regexp" => self.patterns.push(value.clone()),
+            "file" => self.pattern_files.push(value.clone()),
+            "query" => {
+                self.expression = Some(Expr::parse(&value).map_err(|e| invalid(&e.to_string()))?)
+            }
+            "regex" => self.regex = true,
+            "ignore-case" => {
+                self.case_sensitive = false;
+                self.smart_case = false;
+            }
+            "case-sensitive" => {
+                self.case_sensitive = true;
+                self.smart_case = false;
+            }
+            "smart-case" => self.smart_case = true,
+            "fuzzy" => self.fuzzy = Some(number()?),
+            "glob" => self
+                .filter
+                .add_glob(&value)
+                .map_err(|e| invalid(&e.message))?,
+            "type" => self.filter.add_type(&value).map_err(|e| invalid(&e))?,
+            "word-regexp" => self.word_regexp = true,
+            "line-regexp" => self.line_regexp = true,
+            "multiline" => self.multiline = true,
+            "invert-match" => self.invert_match = true,
+            "text" => self.text = true,
+            "max-count" => self.max_count = Some(number()?),
+            "line-number" => self.line_number = true,
+            "byte-offset" => self.byte_offset = true,
+            "after-context" => self.after_context = number()?,
+            "before-context" => self.before_context = number()?,
+            "context" => {
+                self.after_context = number()?;
+                self.before_context = self.after_context;
+            }
+            "count" => self.output = OutputMode::Count,
+            "files-with-matches" => self.output = OutputMode::FilesWithMatches,
+            "files-without-match" => self.output = OutputMode::FilesWithoutMatch,
+            "quiet" => self.output = OutputMode::Quiet,
+            "color" => self.color = value.parse().map_err(invalid)?,
+            "colors" => self.colors.set(&value).map_err(invalid)?,
+            "json" => self.json = true,
+            "replace" => self.replace = Some(value.clone()),
+            "dry-run" => self.dry_run = true,
+            "in-place" => self.in_place = true,
+            "follow" => self.follow = true,
+            "interactive" => self.interactive = true,
+            "threads" => match number()? {
+                0 => return Err(invalid("expected at least one thread")),
+                n => self.threads = n,
+            },
+            "sort" => match value.as_str() {
+                "path" => self.sort_by_path = true,
+                _ => return Err(invalid("the only supported sort key is 'path'")),
+            },
+            "index" => self.index = true,
+            "help" => return Err(ConfigError::Help),
+            "version" => return Err(ConfigError::Version),
+            _ => unreachable!("option --{} is not handled", opt.long),
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+
+    fn parse(args: &[&str]) -> Result<Config, ConfigError> {
+        let args: Vec<String> = std::iter::once("minigrep")
+            .chain(args.iter().cloned())
+            .map(String::from)
+            .collect();
+        Config::new(&args)
+    }
+
+    #[test]
+    fn regex_flag() {
+        let config = parse(&["-E", "fn\\s+", "main.rs"]).unwrap();
+
+        assert!(config.regex);
+        assert_eq!("fn\\s+", config.query);
+        assert_eq!(vec!["main.rs"], config.paths);
+    }
+
+    #[test]
+    fn context_flags() {
+        let config = parse(&["-C", "2", "-A", "3", "to", "poem.txt"]).unwrap();
+
+        assert_eq!(2, config.before_context);
+        assert_eq!(3, config.after_context);
+
+        assert_eq!(
+            Some(ConfigError::InvalidValue {
+                option: "-B".to_string(),
+                value: "x".to_string(),
+                reason: "expected a non-negative integer".to_string(),
+            }),
+            parse(&["to", "poem.txt", "-B", "x"]).err()
+        );
+    }
+
+    #[test]
+    fn threads_and_sort() {
+        let config = parse(&["-j", "4", "--sort", "path", "to", "src"]).unwrap();
+
+        assert_eq!(4, config.threads);
+        assert!(config.sort_by_path);
+
+        let err = parse(&["-j", "0", "to", "src"]).err().unwrap();
+        assert_eq!(
+            "invalid value '0' for '-j': expected at least one thread",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn pattern_flags() {
+        let config = parse(&["-e", "foo", "-ebar", "-f", "tokens.txt", "src"]).unwrap();
+
+        assert_eq!(vec!["foo", "bar"], config.patterns);
+        assert_eq!(vec!["tokens.txt"], config.pattern_files);
+        assert_eq!("", config.query);
+        assert_eq!(vec!["src"], config.paths);
+    }
+
+    #[test]
+    fn output_modes() {
+        let config = parse(&["-v", "-c", "-m", "5", "to", "poem.txt"]).unwrap();
+
+        assert!(config.invert_match);
+        assert_eq!(Some(5), config.max_count);
+        assert_eq!(OutputMode::Count, config.output);
+
+        assert_eq!(
+            OutputMode::Quiet,
+            parse(&["-l", "-q", "to", "poem.txt"]).unwrap().output
+        );
+    }
+
+    #[test]
+    fn color_flags() {
+        let config = parse(&[
+            "--color=always",
+            "--colors",
+            "match:fg:blue",
+            "to",
+            "poem.txt",
+        ])
+        .unwrap();
+
+        assert_eq!(ColorChoice::Always, config.color);
+        assert_eq!(Some(Color::Blue), config.colors.matched.color);
+
+        assert!(parse(&["--color", "sometimes", "to", "poem.txt"]).is_err());
+    }
+
+    #[test]
+    fn multiple_paths() {
+        let config = parse(&["-i", "to", "src", "poem.txt"]).unwrap();
+
+        assert_eq!("to", config.query);
+        assert_eq!(vec!["src", "poem.txt"], config.paths);
+        assert!(!config.case_sensitive);
+    }
+
+    #[test]
+    fn case_flags() {
+        let config = parse(&["-S", "to", "poem.txt"]).unwrap();
+        assert!(config.smart_case);
+
+        let config = parse(&["-S", "-i", "to", "poem.txt"]).unwrap();
+        assert!(!config.smart_case);
+        assert!(!config.case_sensitive);
+    }
+
+    #[test]
+    fn replace_flags() {
+        let config = parse(&["-E", "(a)", "-r", "<$1>", "--dry-run", "f"]).unwrap();
+        assert_eq!(Some(String::from("<$1>")), config.replace);
+        assert!(config.dry_run);
+
+        assert_eq!(
+            "option '--in-place' requires '--replace'",
+            parse(&["--in-place", "a", "f"]).err().unwrap().to_string()
+        );
+        assert_eq!(
+            "option '--in-place' cannot be used with standard input",
+            parse(&["-r", "b", "--in-place", "a"]).err().unwrap().to_string()
+        );
+    }
+
+    #[test]
+    fn fuzzy_flag() {
+        assert_eq!(Some(2), parse(&["--fuzzy=2", "x", "f"]).unwrap().fuzzy);
+        assert_eq!(
+            "option '--fuzzy' cannot be used with '--regex'",
+            parse(&["--fuzzy", "1", "-E", "x", "f"]).err().unwrap().to_string()
+        );
+    }
+
+    #[test]
+    fn glob_and_type_flags() {
+        assert!(parse(&["-g", "*.rs", "--glob=!target/", "-trust", "x", "f"]).is_ok());
+        assert_eq!(
+            Some(ConfigError::InvalidValue {
+                option: "--glob".to_string(),
+                value: "*.{rs".to_string(),
+                reason: "unclosed brace".to_string(),
+            }),
+            parse(&["--glob", "*.{rs", "x", "f"]).err()
+        );
+        assert!(parse(&["--type", "cobol", "x", "f"]).is_err());
+    }
+
+    #[test]
+    fn multiline_flag() {
+        assert!(parse(&["-U", "x", "f"]).unwrap().multiline);
+        assert_eq!(
+            "option '--replace' cannot be used with '--multiline'",
+            parse(&["-U", "-r", "y", "x", "f"]).err().unwrap().to_string()
+        );
+    }
+
+    #[test]
+    fn query_flag() {
+        let config = parse(&["--query", "a AND NOT b", "f"]).unwrap();
+        assert_eq!(Some(Expr::parse("a AND NOT b").unwrap()), config.expression);
+        assert_eq!(vec!["f"], config.paths);
+        assert_eq!(
+            Some(ConfigError::InvalidValue {
+                option: "--query".to_string(),
+                value: "a AND".to_string(),
+                reason: "query parse error at position 5: expected a term".to_string(),
+            }),
+            parse(&["--query", "a AND", "f"]).err()
+        );
+        assert_eq!(
+            "option '--query' cannot be used with '--regexp'",
+            parse(&["--query", "a", "-e", "b", "f"]).err().unwrap().to_string()
+        );
+    }
+
+    #[test]
+    fn follow_flag() {
+        assert!(parse(&["--follow", "x", "f"]).unwrap().follow);
+        assert_eq!(
+            "option '--follow' cannot be used with '--count'",
+            parse(&["--follow", "-c", "x", "f"]).err().unwrap().to_string()
+        );
+        assert_eq!(
+            "option '--follow' cannot be used with standard input",
+            parse(&["--follow", "x"]).err().unwrap().to_string()
+        );
+    }
+
+    #[test]
+    fn interactive_flag() {
+        let config = parse(&["--interactive", "-i", "poem.txt"]).unwrap();
+        assert!(config.interactive);
+        assert_eq!(vec!["poem.txt"], config.paths);
+        assert_eq!(
+            "option '--interactive' cannot be used with more than one FILE",
+            parse(&["--interactive", "a", "b"]).err().unwrap().to_string()
+        );
+        assert_eq!(
+            "option '--interactive' cannot be used with standard input",
+            parse(&["--interactive"]).err().unwrap().to_string()
+        );
+    }
+
+    #[test]
+    fn grouped_short_flags() {
+        let config = parse(&["-nvE", "-A3", "--before-context", "2", "x", "f"]).unwrap();
+
+        assert!(config.line_number);
+        assert!(config.invert_match);
+        assert!(config.regex);
+        assert_eq!(3, config.after_context);
+        assert_eq!(2, config.before_context);
+    }
+
+    #[test]
+    fn reads_standard_input_without_paths() {
+        let config = parse(&["-n", "x"]).unwrap();
+        assert_eq!(vec!["-"], config.paths);
+    }
+
+    #[test]
+    fn double_dash_ends_options() {
+        let config = parse(&["-n", "--", "-v", "--file"]).unwrap();
+
+        assert!(config.line_number);
+        assert!(!config.invert_match);
+        assert_eq!("-v", config.query);
+        assert_eq!(vec!["--file"], config.paths);
+    }
+
+    #[test]
+    fn help_and_version() {
+        assert_eq!(Some(ConfigError::Help), parse(&["--help"]).err());
+        assert_eq!(Some(ConfigError::Version), parse(&["-V"]).err());
+        assert!(usage().contains("--after-context NUM"));
+    }
+
+    #[test]
+    fn typed_errors() {
+        assert_eq!(
+            Some(ConfigError::UnknownOption("--frobnicate".to_string())),
+            parse(&["--frobnicate", "x", "f"]).err()
+        );
+        assert_eq!(
+            Some(ConfigError::UnknownOption("-Z".to_string())),
+            parse(&["-nZ", "x", "f"]).err()
+        );
+        assert_eq!(
+            Some(ConfigError::MissingValue("-A".to_string())),
+            parse(&["x", "f", "-A"]).err()
+        );
+        assert_eq!(
+            Some(ConfigError::UnexpectedValue("--json".to_string())),
+            parse(&["--json=yes", "x", "f"]).err()
+        );
+        assert_eq!(Some(ConfigError::MissingPattern), parse(&[]).err());
+    }
+}
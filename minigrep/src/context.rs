@@ -0,0 +1,192 @@
+use std::collections::VecDeque;
+use std::io;
+use std::ops::Range;
+
+use crate::matcher::Match;
+
+// Surrounds matching lines with the lines before and after them, the way
+// `grep -A`, `-B` and `-C` do. Lines are fed one at a time, so only the
+// last `before` lines are ever kept in memory. Overlapping or adjacent
+// windows are merged and a separator is placed between groups that are
+// not contiguous.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ContextLine<'a> {
+    Match(Match<'a>),
+    Context {
+        line_number: usize,
+        byte_offset: usize,
+        line: &'a str,
+    },
+    Separator,
+}
+
+pub struct Context {
+    before: usize,
+    after: usize,
+    buffer: VecDeque<(usize, usize, String)>,
+    after_remaining: usize,
+    last_printed: Option<usize>,
+}
+
+impl Context {
+    pub fn new(before: usize, after: usize) -> Context {
+        Context {
+            before,
+            after,
+            buffer: VecDeque::with_capacity(before),
+            after_remaining: 0,
+            last_printed: None,
+        }
+    }
+
+    /// Feeds the next line of input, along with whether it matched and the
+    /// occurrences found in it. Every line that should be printed as a
+    /// result is passed to `emit`, in order.
+    pub fn push<F>(
+        &mut self,
+        line_number: usize,
+        byte_offset: usize,
+        line: &str,
+        matched: bool,
+        ranges: Vec<Range<usize>>,
+        emit: &mut F,
+    ) -> io::Result<()>
+    where
+        F: FnMut(ContextLine) -> io::Result<()>,
+    {
+        if !matched {
+            return self.push_non_match(line_number, byte_offset, line, emit);
+        }
+
+        let first = line_number - self.buffer.len();
+        if let Some(last) = self.last_printed {
+            if first > last + 1 && (self.before > 0 || self.after > 0) {
+                emit(ContextLine::Separator)?;
+            }
+        }
+
+        for (line_number, byte_offset, line) in self.buffer.drain(..) {
+            emit(ContextLine::Context {
+                line_number,
+                byte_offset,
+                line: &line,
+            })?;
+        }
+
+        emit(ContextLine::Match(Match {
+            line_number,
+            byte_offset,
+            line,
+            ranges,
+        }))?;
+
+        self.last_printed = Some(line_number);
+        self.after_remaining = self.after;
+        Ok(())
+    }
+
+    fn push_non_match<F>(
+        &mut self,
+        line_number: usize,
+        byte_offset: usize,
+        line: &str,
+        emit: &mut F,
+    ) -> io::Result<()>
+    where
+        F: FnMut(ContextLine) -> io::Result<()>,
+    {
+        if self.after_remaining > 0 {
+            self.after_remaining -= 1;
+            self.last_printed = Some(line_number);
+            return emit(ContextLine::Context {
+                line_number,
+                byte_offset,
+                line,
+            });
+        }
+
+        if self.before > 0 {
+            if self.buffer.len() == self.before {
+                self.buffer.pop_front();
+            }
+            self.buffer
+                .push_back((line_number, byte_offset, line.to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// Whether lines after the last match are still being printed.
+    pub fn in_after_context(&self) -> bool {
+        self.after_remaining > 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LINES: [&str; 8] = ["a", "b", "c", "d", "e", "f", "g", "h"];
+
+    // Renders the output as `number:line` for matches, `number-line` for
+    // context and `--` for separators.
+    fn render(matches: &[usize], before: usize, after: usize) -> Vec<String> {
+        let mut context = Context::new(before, after);
+        let mut output = Vec::new();
+
+        for (i, line) in LINES.iter().enumerate() {
+            let matched = matches.contains(&(i + 1));
+            let ranges = if matched {
+                vec![Range {
+                    start: 0,
+                    end: line.len(),
+                }]
+            } else {
+                Vec::new()
+            };
+            context
+                .push(i + 1, i * 2, line, matched, ranges, &mut |line| {
+                    output.push(match line {
+                        ContextLine::Match(found) => {
+                            format!("{}:{}", found.line_number, found.line)
+                        }
+                        ContextLine::Context {
+                            line_number, line, ..
+                        } => format!("{}-{}", line_number, line),
+                        ContextLine::Separator => "--".to_string(),
+                    });
+                    Ok(())
+                })
+                .unwrap();
+        }
+
+        output
+    }
+
+    #[test]
+    fn no_context() {
+        assert_eq!(vec!["2:b", "6:f"], render(&[2, 6], 0, 0));
+    }
+
+    #[test]
+    fn separates_distant_groups() {
+        assert_eq!(
+            vec!["1-a", "2:b", "3-c", "--", "6-f", "7:g", "8-h"],
+            render(&[2, 7], 1, 1)
+        );
+    }
+
+    #[test]
+    fn merges_overlapping_windows() {
+        assert_eq!(
+            vec!["1-a", "2:b", "3-c", "4:d", "5-e", "6-f"],
+            render(&[2, 4], 1, 2)
+        );
+    }
+
+    #[test]
+    fn adjacent_windows_have_no_separator() {
+        assert_eq!(vec!["1:a", "2-b", "3:c", "4-d"], render(&[1, 3], 0, 1));
+    }
+}
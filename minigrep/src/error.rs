@@ -0,0 +1,105 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::regex::RegexError;
+
+/// Everything that can go wrong while searching, along with the path or
+/// pattern involved.
+#[derive(Debug)]
+pub enum MinigrepError {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    PermissionDenied {
+        path: PathBuf,
+    },
+    InvalidUtf8 {
+        path: PathBuf,
+    },
+    /// A pattern that does not compile. `path` is the `-f` file it came
+    /// from, if any.
+    Pattern {
+        path: Option<PathBuf>,
+        pattern: String,
+        source: RegexError,
+    },
+    /// Some of several files could not be searched; each failure has
+    /// already been reported.
+    Incomplete {
+        failed: usize,
+    },
+}
+
+impl MinigrepError {
+    /// Wraps an I/O error raised while reading `path`, picking out the
+    /// kinds that deserve a variant of their own.
+    pub fn io(path: &Path, source: io::Error) -> MinigrepError {
+        let path = path.to_path_buf();
+        match source.kind() {
+            io::ErrorKind::PermissionDenied => MinigrepError::PermissionDenied { path },
+            io::ErrorKind::InvalidData => MinigrepError::InvalidUtf8 { path },
+            _ => MinigrepError::Io { path, source },
+        }
+    }
+}
+
+impl fmt::Display for MinigrepError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MinigrepError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            MinigrepError::PermissionDenied { path } => {
+                write!(f, "{}: permission denied", path.display())
+            }
+            MinigrepError::InvalidUtf8 { path } => {
+                write!(f, "{}: file is not valid UTF-8", path.display())
+            }
+            MinigrepError::Pattern {
+                path,
+                pattern,
+                source,
+            } => {
+                if let Some(path) = path {
+                    write!(f, "{}: ", path.display())?;
+                }
+                write!(f, "invalid pattern '{}': {}", pattern, source)
+            }
+            MinigrepError::Incomplete { failed: 1 } => write!(f, "1 file could not be searched"),
+            MinigrepError::Incomplete { failed } => {
+                write!(f, "{} files could not be searched", failed)
+            }
+        }
+    }
+}
+
+impl Error for MinigrepError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MinigrepError::Io { source, .. } => Some(source),
+            MinigrepError::Pattern { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_io_errors() {
+        let path = Path::new("secret.txt");
+
+        let err = MinigrepError::io(path, io::Error::from(io::ErrorKind::PermissionDenied));
+        assert_eq!("secret.txt: permission denied", err.to_string());
+
+        let err = MinigrepError::io(path, io::Error::new(io::ErrorKind::InvalidData, "bad"));
+        assert_eq!("secret.txt: file is not valid UTF-8", err.to_string());
+
+        let err = MinigrepError::io(path, io::Error::new(io::ErrorKind::NotFound, "gone"));
+        assert_eq!("secret.txt: gone", err.to_string());
+        assert!(err.source().is_some());
+    }
+}
@@ -0,0 +1,340 @@
+use std::error::Error;
+use std::fmt;
+
+// Boolean query expressions for `--query`, such as
+//
+//   timeout AND db AND NOT retry
+//   (error OR warning) AND NOT "known issue"
+//
+// NOT binds tighter than AND, which binds tighter than OR, and parentheses
+// group. The operators are only recognized in capitals; a term that is
+// one of them, or that holds spaces, parentheses or quotes, can be put in
+// double quotes, with `\"` and `\\` inside standing for `"` and `\`.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExprError {
+    pub message: String,
+    pub position: usize,
+}
+
+impl fmt::Display for ExprError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "query parse error at position {}: {}",
+            self.position, self.message
+        )
+    }
+}
+
+impl Error for ExprError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr<T> {
+    Term(T),
+    Not(Box<Expr<T>>),
+    And(Vec<Expr<T>>),
+    Or(Vec<Expr<T>>),
+}
+
+impl Expr<String> {
+    pub fn parse(query: &str) -> Result<Expr<String>, ExprError> {
+        let mut parser = Parser {
+            tokens: tokenize(query)?,
+            pos: 0,
+            end: query.chars().count(),
+        };
+        let expr = parser.parse_or()?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(expr),
+            Some((Token::Close, position)) => Err(error("unmatched ')'", *position)),
+            Some((_, position)) => Err(error("expected AND or OR", *position)),
+        }
+    }
+}
+
+impl<T> Expr<T> {
+    /// Builds the same expression over new terms.
+    pub fn try_map<U, E, F>(&self, f: &mut F) -> Result<Expr<U>, E>
+    where
+        F: FnMut(&T) -> Result<U, E>,
+    {
+        let all = |exprs: &[Expr<T>], f: &mut F| {
+            exprs
+                .iter()
+                .map(|expr| expr.try_map(f))
+                .collect::<Result<Vec<_>, E>>()
+        };
+
+        Ok(match self {
+            Expr::Term(term) => Expr::Term(f(term)?),
+            Expr::Not(expr) => Expr::Not(Box::new(expr.try_map(f)?)),
+            Expr::And(exprs) => Expr::And(all(exprs, f)?),
+            Expr::Or(exprs) => Expr::Or(all(exprs, f)?),
+        })
+    }
+
+    /// Whether the expression holds, given which terms do.
+    pub fn eval<F: Fn(&T) -> bool>(&self, holds: &F) -> bool {
+        match self {
+            Expr::Term(term) => holds(term),
+            Expr::Not(expr) => !expr.eval(holds),
+            Expr::And(exprs) => exprs.iter().all(|expr| expr.eval(holds)),
+            Expr::Or(exprs) => exprs.iter().any(|expr| expr.eval(holds)),
+        }
+    }
+
+    /// The terms not under a NOT, which are the ones worth highlighting.
+    pub fn positive_terms(&self) -> Vec<&T> {
+        let mut terms = Vec::new();
+        self.collect_positive(&mut terms);
+        terms
+    }
+
+    fn collect_positive<'a>(&'a self, terms: &mut Vec<&'a T>) {
+        match self {
+            Expr::Term(term) => terms.push(term),
+            Expr::Not(_) => {}
+            Expr::And(exprs) | Expr::Or(exprs) => {
+                for expr in exprs {
+                    expr.collect_positive(terms);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Term(String),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+fn error(message: &str, position: usize) -> ExprError {
+    ExprError {
+        message: message.to_string(),
+        position,
+    }
+}
+
+// Splits the query into tokens, each with the character position it
+// starts at.
+fn tokenize(query: &str) -> Result<Vec<(Token, usize)>, ExprError> {
+    let chars: Vec<char> = query.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let start = i;
+        match chars[i] {
+            c if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '(' => tokens.push((Token::Open, start)),
+            ')' => tokens.push((Token::Close, start)),
+            '"' => {
+                let mut term = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => return Err(error("unclosed quote", start)),
+                        Some('"') => break,
+                        Some('\\') if matches!(chars.get(i + 1), Some('"') | Some('\\')) => {
+                            term.push(chars[i + 1]);
+                            i += 1;
+                        }
+                        Some(&c) => term.push(c),
+                    }
+                    i += 1;
+                }
+                tokens.push((Token::Term(term), start));
+            }
+            _ => {
+                while i < chars.len() && !chars[i].is_whitespace() && !"()\"".contains(chars[i]) {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+                let token = match word.as_str() {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    _ => Token::Term(word),
+                };
+                tokens.push((token, start));
+                continue;
+            }
+        }
+        i += 1;
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    // The position just past the query, for errors at its end.
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .map_or(self.end, |&(_, position)| position)
+    }
+
+    fn parse_or(&mut self) -> Result<Expr<String>, ExprError> {
+        let mut exprs = vec![self.parse_and()?];
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            exprs.push(self.parse_and()?);
+        }
+        Ok(if exprs.len() == 1 {
+            exprs.pop().unwrap()
+        } else {
+            Expr::Or(exprs)
+        })
+    }
+
+    fn parse_and(&mut self) -> Result<Expr<String>, ExprError> {
+        let mut exprs = vec![self.parse_not()?];
+        while self.peek() == Some(&Token::And) {
+            self.pos += 1;
+            exprs.push(self.parse_not()?);
+        }
+        Ok(if exprs.len() == 1 {
+            exprs.pop().unwrap()
+        } else {
+            Expr::And(exprs)
+        })
+    }
+
+    fn parse_not(&mut self) -> Result<Expr<String>, ExprError> {
+        if self.peek() == Some(&Token::Not) {
+            self.pos += 1;
+            return Ok(Expr::Not(Box::new(self.parse_not()?)));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr<String>, ExprError> {
+        let position = self.position();
+        match self.tokens.get(self.pos).map(|(token, _)| token.clone()) {
+            Some(Token::Term(term)) => {
+                self.pos += 1;
+                Ok(Expr::Term(term))
+            }
+            Some(Token::Open) => {
+                self.pos += 1;
+                let expr = self.parse_or()?;
+                if self.peek() != Some(&Token::Close) {
+                    return Err(error("unclosed '('", position));
+                }
+                self.pos += 1;
+                Ok(expr)
+            }
+            _ => Err(error("expected a term", position)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn term(s: &str) -> Expr<String> {
+        Expr::Term(s.to_string())
+    }
+
+    fn not(expr: Expr<String>) -> Expr<String> {
+        Expr::Not(Box::new(expr))
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(
+            Ok(Expr::And(vec![
+                term("timeout"),
+                term("db"),
+                not(term("retry"))
+            ])),
+            Expr::parse("timeout AND db AND NOT retry")
+        );
+        assert_eq!(
+            Ok(Expr::Or(vec![
+                term("a"),
+                Expr::And(vec![term("b"), term("c")]),
+            ])),
+            Expr::parse("a OR b AND c")
+        );
+        assert_eq!(
+            Ok(Expr::And(vec![
+                Expr::Or(vec![term("a"), term("b")]),
+                not(not(term("c"))),
+            ])),
+            Expr::parse("(a OR b) AND NOT NOT c")
+        );
+    }
+
+    #[test]
+    fn quoted_terms() {
+        assert_eq!(
+            Ok(Expr::And(vec![
+                term("known issue"),
+                term("AND"),
+                term(r#"say "hi"\"#)
+            ])),
+            Expr::parse(r#""known issue" AND "AND" AND "say \"hi\"\\""#)
+        );
+        assert_eq!(Ok(term("and")), Expr::parse("and"));
+    }
+
+    #[test]
+    fn parse_errors() {
+        let message = |query| Expr::parse(query).unwrap_err().to_string();
+        assert_eq!(
+            "query parse error at position 7: expected a term",
+            message("a AND ()")
+        );
+        assert_eq!(
+            "query parse error at position 2: expected AND or OR",
+            message("a b")
+        );
+        assert_eq!(
+            "query parse error at position 0: unclosed '('",
+            message("(a OR b")
+        );
+        assert_eq!(
+            "query parse error at position 1: unmatched ')'",
+            message("a)")
+        );
+        assert_eq!(
+            "query parse error at position 0: unclosed quote",
+            message("\"a")
+        );
+        assert_eq!(
+            "query parse error at position 6: expected a term",
+            message("a AND ")
+        );
+    }
+
+    #[test]
+    fn evaluates_against_terms() {
+        let expr = Expr::parse("timeout AND db AND NOT retry").unwrap();
+        let line = |text: &'static str| move |term: &String| text.contains(term.as_str());
+        assert!(expr.eval(&line("db timeout after 5s")));
+        assert!(!expr.eval(&line("db timeout, retry 1")));
+        assert!(!expr.eval(&line("timeout")));
+        assert_eq!(vec!["timeout", "db"], expr.positive_terms());
+    }
+}
@@ -0,0 +1,149 @@
+use std::fs::{self, File, Metadata};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+// Reads a file that is still being written, for `--follow`. Reaching the
+// end does not end the input: the reader waits for more to be appended,
+// checking the file every so often. A file that shrinks was truncated and
+// is read again from the start. When the path comes to name a different
+// file, as after log rotation, whatever is left of the old file is read
+// before switching to the new one. To the search it is all one input, so
+// line numbers keep counting across both.
+
+pub const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+pub struct Follower {
+    path: PathBuf,
+    file: File,
+    id: Option<(u64, u64)>,
+    position: u64,
+    interval: Duration,
+}
+
+impl Follower {
+    pub fn open(path: &Path, interval: Duration) -> io::Result<Follower> {
+        let file = File::open(path)?;
+        let id = file_id(&file.metadata()?);
+        Ok(Follower {
+            path: path.to_path_buf(),
+            file,
+            id,
+            position: 0,
+            interval,
+        })
+    }
+
+    // Called at the end of the open file; returns once there may be more
+    // to read.
+    fn wait(&mut self) -> io::Result<()> {
+        loop {
+            let metadata = match fs::metadata(&self.path) {
+                Ok(metadata) => metadata,
+                // Between a rotation moving the file away and a new one
+                // being created, there is nothing to follow.
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    thread::sleep(self.interval);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+
+            if file_id(&metadata) != self.id {
+                eprintln!(
+                    "minigrep: {}: file replaced; following the new file",
+                    self.path.display()
+                );
+                self.file = File::open(&self.path)?;
+                self.id = file_id(&self.file.metadata()?);
+                self.position = 0;
+                return Ok(());
+            }
+            if metadata.len() < self.position {
+                eprintln!("minigrep: {}: file truncated", self.path.display());
+                self.position = self.file.seek(SeekFrom::Start(0))?;
+                return Ok(());
+            }
+            if metadata.len() > self.position {
+                return Ok(());
+            }
+            thread::sleep(self.interval);
+        }
+    }
+}
+
+impl Read for Follower {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let read = self.file.read(buf)?;
+            if read > 0 {
+                self.position += read as u64;
+                return Ok(read);
+            }
+            self.wait()?;
+        }
+    }
+}
+
+// What identifies the file itself rather than its name. Where there is no
+// such thing, only truncation is noticed.
+#[cfg(unix)]
+fn file_id(metadata: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(_metadata: &Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::process;
+
+    #[test]
+    fn follows_appends_truncation_and_rotation() {
+        let dir = std::env::temp_dir().join(format!("minigrep-follow-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("app.log");
+        fs::write(&path, "one\n").unwrap();
+
+        let mut lines = BufReader::new(Follower::open(&path, Duration::from_millis(5)).unwrap())
+            .lines()
+            .map(Result::unwrap);
+        assert_eq!(Some("one".to_string()), lines.next());
+
+        let writer = {
+            let path = path.clone();
+            thread::spawn(move || {
+                let pause = || thread::sleep(Duration::from_millis(50));
+                pause();
+                let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+                file.write_all(b"tw").unwrap();
+                pause();
+                file.write_all(b"o\n").unwrap();
+                pause();
+                fs::write(&path, "").unwrap();
+                pause();
+                fs::write(&path, "three\n").unwrap();
+                pause();
+                fs::rename(&path, path.with_extension("log.1")).unwrap();
+                fs::write(&path, "four\n").unwrap();
+            })
+        };
+
+        assert_eq!(Some("two".to_string()), lines.next());
+        assert_eq!(Some("three".to_string()), lines.next());
+        assert_eq!(Some("four".to_string()), lines.next());
+        writer.join().unwrap();
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
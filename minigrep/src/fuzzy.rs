@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::case;
+
+// Approximate matching for `--fuzzy`: finds substrings within a given
+// Levenshtein distance of the query. Uses Myers' bit-vector algorithm,
+// which keeps a column of the edit distance table as bits saying whether
+// each cell is one more or one less than the cell above it, and moves to
+// the next column with a handful of word operations. Queries longer than
+// a word are split into blocks of 64 rows that pass their carries down.
+
+const WORD_BITS: usize = 64;
+
+#[derive(Debug, Clone)]
+pub struct Fuzzy {
+    length: usize,
+    max_distance: usize,
+    case_insensitive: bool,
+    // For each character in the query, the rows where it appears.
+    rows: HashMap<char, Vec<u64>>,
+    // The same for the query reversed, to find where matches start.
+    rows_reversed: HashMap<char, Vec<u64>>,
+}
+
+impl Fuzzy {
+    pub fn new(query: &str, max_distance: usize, case_insensitive: bool) -> Fuzzy {
+        let fold = |c| {
+            if case_insensitive {
+                case::fold_simple(c)
+            } else {
+                c
+            }
+        };
+        let query: Vec<char> = query.chars().map(fold).collect();
+
+        Fuzzy {
+            length: query.len(),
+            max_distance,
+            case_insensitive,
+            rows: rows(query.iter().cloned()),
+            rows_reversed: rows(query.iter().rev().cloned()),
+        }
+    }
+
+    fn fold(&self, c: char) -> char {
+        if self.case_insensitive {
+            case::fold_simple(c)
+        } else {
+            c
+        }
+    }
+
+    /// Finds the first approximate occurrence that ends at or after byte
+    /// `start`, along with its distance from the query. Once a substring
+    /// is close enough, the end is pushed on for as long as that keeps it
+    /// at least as close.
+    pub fn find_at(&self, text: &str, start: usize) -> Option<(Range<usize>, usize)> {
+        let mut column = Column::new(&self.rows, self.length, false);
+        let mut best = if self.length <= self.max_distance {
+            Some((start, self.length))
+        } else {
+            None
+        };
+
+        for (i, c) in text[start..].char_indices() {
+            let distance = column.next(self.fold(c));
+            match best {
+                Some((_, closest)) if distance > closest => break,
+                _ if distance <= self.max_distance => {
+                    best = Some((start + i + c.len_utf8(), distance))
+                }
+                _ => {}
+            }
+        }
+
+        let (end, distance) = best?;
+        Some((self.start_of(&text[start..end], distance) + start..end, distance))
+    }
+
+    // Walks `text` backwards from its end, matching the reversed query
+    // against it, until the distance found going forwards is reached.
+    fn start_of(&self, text: &str, distance: usize) -> usize {
+        if self.length == distance {
+            return text.len();
+        }
+
+        let mut column = Column::new(&self.rows_reversed, self.length, true);
+        for (i, c) in text.char_indices().rev() {
+            if column.next(self.fold(c)) == distance {
+                return i;
+            }
+        }
+        0
+    }
+
+    /// The distance of the closest substring of `text` to the query, if it
+    /// is close enough.
+    pub fn distance(&self, text: &str) -> Option<usize> {
+        let mut column = Column::new(&self.rows, self.length, false);
+        let closest = text
+            .chars()
+            .map(|c| column.next(self.fold(c)))
+            .fold(self.length, usize::min);
+
+        if closest <= self.max_distance {
+            Some(closest)
+        } else {
+            None
+        }
+    }
+}
+
+fn rows<I: Iterator<Item = char>>(query: I) -> HashMap<char, Vec<u64>> {
+    let query: Vec<char> = query.collect();
+    let blocks = query.len().div_ceil(WORD_BITS);
+    let mut rows = HashMap::new();
+
+    for (i, c) in query.into_iter().enumerate() {
+        let bits = rows.entry(c).or_insert_with(|| vec![0; blocks]);
+        bits[i / WORD_BITS] |= 1 << (i % WORD_BITS);
+    }
+    rows
+}
+
+// One column of the edit distance table, kept as the vertical differences
+// between its cells, one bit per row.
+struct Column<'f> {
+    rows: &'f HashMap<char, Vec<u64>>,
+    // Rows where the cell is one more (`plus`) or one less (`minus`) than
+    // the cell above it.
+    plus: Vec<u64>,
+    minus: Vec<u64>,
+    // The bit of the query's last row in the last block.
+    last_row: u64,
+    // The value of the cell in the last row.
+    distance: usize,
+    // Whether the match must start at the first character fed in, rather
+    // than anywhere.
+    anchored: bool,
+}
+
+impl<'f> Column<'f> {
+    fn new(rows: &'f HashMap<char, Vec<u64>>, length: usize, anchored: bool) -> Column<'f> {
+        let blocks = length.div_ceil(WORD_BITS);
+        Column {
+            rows,
+            plus: vec![!0; blocks],
+            minus: vec![0; blocks],
+            last_row: 1 << ((length.max(1) - 1) % WORD_BITS),
+            distance: length,
+            anchored,
+        }
+    }
+
+    // Moves to the next column and returns its distance.
+    fn next(&mut self, c: char) -> usize {
+        let matches = self.rows.get(&c);
+        // The change along the top row: none when a match may start
+        // anywhere, one per character when it must start at the first.
+        let mut carry = if self.anchored { 1 } else { 0 };
+
+        for block in 0..self.plus.len() {
+            let high = if block + 1 == self.plus.len() {
+                self.last_row
+            } else {
+                1 << (WORD_BITS - 1)
+            };
+            let eq = matches.map_or(0, |bits| bits[block]);
+            carry = self.advance(block, eq, carry, high);
+        }
+
+        self.distance = (self.distance as isize + carry as isize) as usize;
+        self.distance
+    }
+
+    // Updates one block given the characters it matches and the horizontal
+    // change coming into its top row, and returns the change coming out of
+    // the row at `high`.
+    fn advance(&mut self, block: usize, mut eq: u64, carry_in: i8, high: u64) -> i8 {
+        let plus = self.plus[block];
+        let minus = self.minus[block];
+
+        let xv = eq | minus;
+        if carry_in < 0 {
+            eq |= 1;
+        }
+        let xh = ((eq & plus).wrapping_add(plus) ^ plus) | eq;
+        let mut horizontal_plus = minus | !(xh | plus);
+        let mut horizontal_minus = plus & xh;
+
+        let carry_out = if horizontal_plus & high != 0 {
+            1
+        } else if horizontal_minus & high != 0 {
+            -1
+        } else {
+            0
+        };
+
+        horizontal_plus <<= 1;
+        horizontal_minus <<= 1;
+        if carry_in < 0 {
+            horizontal_minus |= 1;
+        } else if carry_in > 0 {
+            horizontal_plus |= 1;
+        }
+
+        self.plus[block] = horizontal_minus | !(xv | horizontal_plus);
+        self.minus[block] = horizontal_plus & xv;
+        carry_out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The distance of the closest substring, by filling in the whole table.
+    fn naive_distance(query: &str, text: &str) -> usize {
+        let query: Vec<char> = query.chars().collect();
+        let mut previous: Vec<usize> = (0..=query.len()).collect();
+        let mut closest = query.len();
+
+        for c in text.chars() {
+            let mut column = vec![0; query.len() + 1];
+            for i in 1..=query.len() {
+                let substitution = previous[i - 1] + (query[i - 1] != c) as usize;
+                column[i] = substitution.min(previous[i] + 1).min(column[i - 1] + 1);
+            }
+            closest = closest.min(column[query.len()]);
+            previous = column;
+        }
+        closest
+    }
+
+    #[test]
+    fn finds_typos() {
+        let fuzzy = Fuzzy::new("receive", 1, false);
+        assert_eq!(Some((4..10, 1)), fuzzy.find_at("did recive it", 0));
+        assert_eq!(Some((4..11, 0)), fuzzy.find_at("did receive it", 0));
+        // Swapping two letters takes two edits.
+        assert_eq!(None, fuzzy.find_at("did recieve it", 0));
+        assert_eq!(Some(2), Fuzzy::new("receive", 2, false).distance("did recieve it"));
+    }
+
+    #[test]
+    fn agrees_with_the_full_table() {
+        let texts = [
+            "the quick brown fox jumps over the lazy dog",
+            "kitten sitting on the mitten",
+            "aaaaabaaaaacaaaaa",
+            "",
+        ];
+        let queries = ["fox", "sittin", "abc", "dog jumps", "zzz", "aaaaaaaaaa"];
+
+        for text in texts.iter() {
+            for query in queries.iter() {
+                let fuzzy = Fuzzy::new(query, 100, false);
+                assert_eq!(
+                    Some(naive_distance(query, text)),
+                    fuzzy.distance(text),
+                    "{:?} in {:?}",
+                    query,
+                    text
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn long_queries_span_blocks() {
+        let query: String = (0..150).map(|i| (b'a' + (i % 26) as u8) as char).collect();
+        let mut text = query.clone();
+        text.replace_range(70..71, "!");
+        text.remove(130);
+        let text = format!("prefix {} suffix", text);
+
+        let fuzzy = Fuzzy::new(&query, 3, false);
+        assert_eq!(Some(naive_distance(&query, &text)), fuzzy.distance(&text));
+        assert_eq!(Some((7..156, 2)), fuzzy.find_at(&text, 0));
+    }
+
+    #[test]
+    fn ignores_case_when_asked() {
+        let fuzzy = Fuzzy::new("Straße", 1, true);
+        assert_eq!(Some((0..7, 0)), fuzzy.find_at("STRAßE", 0));
+    }
+}
@@ -0,0 +1,327 @@
+use std::error::Error;
+use std::fmt;
+
+// Shell-style wildcard patterns matched against `/` separated paths.
+//
+//   `?`      any single character except `/`
+//   `*`      any run of characters except `/`
+//   `**`     as a whole path component, any number of directories
+//   `[abc]`  `[a-z]` `[!a-z]` character classes
+//   `{a,b}`  either alternative; alternatives may hold wildcards and nest
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlobError {
+    pub message: String,
+    pub pattern: String,
+}
+
+impl fmt::Display for GlobError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid glob '{}': {}", self.pattern, self.message)
+    }
+}
+
+impl Error for GlobError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Literal(char),
+    Any,
+    Star,
+    // `**/` at the start of the pattern.
+    RecursivePrefix,
+    // `/**` at the end of the pattern.
+    RecursiveSuffix,
+    // `/**/` in the middle of the pattern.
+    RecursiveZeroOrMore,
+    // `**` as the whole pattern.
+    Everything,
+    Class {
+        ranges: Vec<(char, char)>,
+        negated: bool,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Glob {
+    pattern: String,
+    // One token list for each way of expanding the braces.
+    alternatives: Vec<Vec<Token>>,
+}
+
+impl Glob {
+    pub fn new(pattern: &str) -> Result<Glob, GlobError> {
+        let error = |message: &str| GlobError {
+            message: message.to_string(),
+            pattern: pattern.to_string(),
+        };
+
+        let chars: Vec<char> = pattern.chars().collect();
+        let alternatives = expand_braces(&chars)
+            .ok_or_else(|| error("unclosed brace"))?
+            .iter()
+            .map(|expanded| tokenize(expanded).map_err(error))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Glob {
+            pattern: pattern.to_string(),
+            alternatives,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    pub fn is_match(&self, path: &str) -> bool {
+        let path: Vec<char> = path.chars().collect();
+        self.alternatives
+            .iter()
+            .any(|tokens| matches(tokens, &path))
+    }
+}
+
+impl fmt::Display for Glob {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.pattern)
+    }
+}
+
+// Every pattern the first brace group of `chars` stands for, and so on
+// for the groups after it. Returns None if a brace is never closed.
+fn expand_braces(chars: &[char]) -> Option<Vec<Vec<char>>> {
+    let open = match open_brace(chars) {
+        Some(open) => open,
+        None => return Some(vec![chars.to_vec()]),
+    };
+
+    // The commas splitting the group and the brace closing it.
+    let mut splits = vec![open];
+    let mut depth = 0;
+    let mut i = open + 1;
+    let close = loop {
+        match *chars.get(i)? {
+            '\\' => i += 1,
+            '[' => i = class_end(chars, i).unwrap_or(i),
+            '{' => depth += 1,
+            '}' if depth == 0 => break i,
+            '}' => depth -= 1,
+            ',' if depth == 0 => splits.push(i),
+            _ => {}
+        }
+        i += 1;
+    };
+    splits.push(close);
+
+    let (prefix, suffix) = (&chars[..open], &chars[close + 1..]);
+    let mut expanded = Vec::new();
+    for pair in splits.windows(2) {
+        let alternative = &chars[pair[0] + 1..pair[1]];
+        let joined: Vec<char> = prefix
+            .iter()
+            .chain(alternative)
+            .chain(suffix)
+            .cloned()
+            .collect();
+        expanded.extend(expand_braces(&joined)?);
+    }
+    Some(expanded)
+}
+
+// The first `{` that is neither escaped nor inside a character class.
+fn open_brace(chars: &[char]) -> Option<usize> {
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            '[' => i = class_end(chars, i).unwrap_or(i),
+            '{' => return Some(i),
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+fn class_end(chars: &[char], start: usize) -> Option<usize> {
+    parse_class(chars, start).map(|(_, end)| end)
+}
+
+fn tokenize(chars: &[char]) -> Result<Vec<Token>, &'static str> {
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '?' => tokens.push(Token::Any),
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                let at_start = i == 0;
+                let after_slash = i > 0 && chars[i - 1] == '/';
+                let at_end = i + 2 == chars.len();
+                let before_slash = chars.get(i + 2) == Some(&'/');
+
+                if at_start && at_end {
+                    tokens.push(Token::Everything);
+                } else if at_start && before_slash {
+                    tokens.push(Token::RecursivePrefix);
+                    i += 1;
+                } else if after_slash && at_end {
+                    tokens.pop();
+                    tokens.push(Token::RecursiveSuffix);
+                } else if after_slash && before_slash {
+                    tokens.pop();
+                    tokens.push(Token::RecursiveZeroOrMore);
+                    i += 1;
+                } else {
+                    tokens.push(Token::Star);
+                }
+                i += 1;
+            }
+            '*' => tokens.push(Token::Star),
+            '[' => {
+                let (token, end) = parse_class(chars, i).ok_or("unclosed character class")?;
+                tokens.push(token);
+                i = end;
+            }
+            '\\' => {
+                i += 1;
+                match chars.get(i) {
+                    Some(&c) => tokens.push(Token::Literal(c)),
+                    None => return Err("trailing backslash"),
+                }
+            }
+            c => tokens.push(Token::Literal(c)),
+        }
+        i += 1;
+    }
+
+    Ok(tokens)
+}
+
+// Parses the class starting at `chars[start] == '['` and returns it with
+// the index of the closing bracket.
+fn parse_class(chars: &[char], start: usize) -> Option<(Token, usize)> {
+    let mut i = start + 1;
+    let mut negated = false;
+    let mut ranges = Vec::new();
+
+    if let Some('!') | Some('^') = chars.get(i) {
+        negated = true;
+        i += 1;
+    }
+
+    let first = i;
+    loop {
+        let c = *chars.get(i)?;
+        if c == ']' && i > first {
+            return Some((Token::Class { ranges, negated }, i));
+        }
+        if chars.get(i + 1) == Some(&'-') && chars.get(i + 2).is_some_and(|&end| end != ']') {
+            ranges.push((c, chars[i + 2]));
+            i += 3;
+        } else {
+            ranges.push((c, c));
+            i += 1;
+        }
+    }
+}
+
+fn matches(tokens: &[Token], path: &[char]) -> bool {
+    let token = match tokens.first() {
+        Some(token) => token,
+        None => return path.is_empty(),
+    };
+    let rest = &tokens[1..];
+
+    match token {
+        Token::Literal(c) => path.first() == Some(c) && matches(rest, &path[1..]),
+        Token::Any => path.first().is_some_and(|&c| c != '/') && matches(rest, &path[1..]),
+        Token::Class { ranges, negated } => match path.first() {
+            Some(&c) if c != '/' => {
+                let found = ranges.iter().any(|&(start, end)| start <= c && c <= end);
+                found != *negated && matches(rest, &path[1..])
+            }
+            _ => false,
+        },
+        Token::Star => {
+            for i in 0..=path.len() {
+                if matches(rest, &path[i..]) {
+                    return true;
+                }
+                if i < path.len() && path[i] == '/' {
+                    return false;
+                }
+            }
+            false
+        }
+        Token::Everything => true,
+        Token::RecursivePrefix => {
+            matches(rest, path)
+                || path
+                    .iter()
+                    .enumerate()
+                    .any(|(i, &c)| c == '/' && matches(rest, &path[i + 1..]))
+        }
+        Token::RecursiveSuffix => path.first() == Some(&'/'),
+        Token::RecursiveZeroOrMore => {
+            path.first() == Some(&'/')
+                && path
+                    .iter()
+                    .enumerate()
+                    .any(|(i, &c)| c == '/' && matches(rest, &path[i + 1..]))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_match(pattern: &str, path: &str) -> bool {
+        Glob::new(pattern).unwrap().is_match(path)
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(is_match("*.rs", "main.rs"));
+        assert!(!is_match("*.rs", "src/main.rs"));
+        assert!(is_match("ma?n.rs", "main.rs"));
+        assert!(is_match("[a-m]ain.rs", "main.rs"));
+        assert!(!is_match("[!a-m]ain.rs", "main.rs"));
+        assert!(is_match(r"\*.rs", "*.rs"));
+    }
+
+    #[test]
+    fn recursive_wildcards() {
+        assert!(is_match("**", "a/b/c"));
+        assert!(is_match("**/*.rs", "main.rs"));
+        assert!(is_match("**/*.rs", "src/bin/main.rs"));
+        assert!(is_match("target/**", "target/debug/minigrep"));
+        assert!(!is_match("target/**", "target"));
+        assert!(is_match("src/**/mod.rs", "src/mod.rs"));
+        assert!(is_match("src/**/mod.rs", "src/a/b/mod.rs"));
+        assert!(!is_match("src/**/mod.rs", "srcmod.rs"));
+    }
+
+    #[test]
+    fn brace_alternation() {
+        assert!(is_match("*.{rs,toml}", "Cargo.toml"));
+        assert!(is_match("*.{rs,toml}", "main.rs"));
+        assert!(!is_match("*.{rs,toml}", "main.rs.bak"));
+        assert!(is_match("{src,tests}/**/*.rs", "tests/cli.rs"));
+        assert!(is_match("a{b,c{d,e}}f", "acef"));
+        assert!(is_match("a{,b}c", "ac"));
+        assert!(is_match("[{]x}", "{x}"));
+        assert!(is_match(r"\{a,b}", "{a,b}"));
+    }
+
+    #[test]
+    fn invalid_patterns() {
+        assert!(Glob::new("[abc").is_err());
+        assert!(Glob::new("abc\\").is_err());
+        assert_eq!(
+            "invalid glob '*.{rs': unclosed brace",
+            Glob::new("*.{rs").unwrap_err().to_string()
+        );
+    }
+}
@@ -0,0 +1,535 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::UNIX_EPOCH;
+
+use crate::case;
+use crate::error::MinigrepError;
+use crate::walk::{self, Filter};
+use crate::{Config, OutputMode};
+
+// A trigram index over a directory tree, kept in `.minigrep-index` at its
+// root. For every file it records the size, modification time and the set
+// of ASCII trigrams, three characters in a row, found on its lines once
+// case folded. Folding first means a trigram a pattern needs is there
+// whether the search ignores case or not, even where folding turns `ß`
+// into `ss`. A query can then skip the files that lack a trigram it needs. Files whose
+// size or modification time no longer agree with the index are indexed
+// again, and searched, whatever the index said about them.
+//
+// The file format, with every number little endian:
+//
+//   "minigrep-index 1\n"
+//   u32 number of files, then for each file:
+//     u32 length of the path, the path relative to the root in UTF-8,
+//     u64 size, u64 seconds and u32 nanoseconds of the modification time,
+//     u32 number of trigrams, then each trigram as a u32, in order
+
+pub const INDEX_FILE: &str = ".minigrep-index";
+
+const MAGIC: &[u8] = b"minigrep-index 1\n";
+
+#[derive(Debug, Clone, PartialEq)]
+struct Entry {
+    size: u64,
+    modified: (u64, u32),
+    trigrams: Vec<u32>,
+}
+
+#[derive(Debug)]
+pub struct Index {
+    root: PathBuf,
+    files: BTreeMap<String, Entry>,
+}
+
+impl Index {
+    /// Reads the index of `root`, or starts an empty one if there is none
+    /// or it cannot be understood.
+    pub fn load(root: &Path) -> Result<Index, MinigrepError> {
+        let path = root.join(INDEX_FILE);
+        let files = match fs::read(&path) {
+            Ok(bytes) => decode(&bytes).unwrap_or_default(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(MinigrepError::io(&path, e)),
+        };
+
+        Ok(Index {
+            root: root.to_path_buf(),
+            files,
+        })
+    }
+
+    /// Brings the index in line with `files`, which must all be under the
+    /// root: new and changed files are read and indexed, and files that are
+    /// gone are forgotten. Returns how many files were added, changed or
+    /// forgotten.
+    pub fn update(&mut self, files: &[PathBuf]) -> Result<usize, MinigrepError> {
+        let mut current = BTreeMap::new();
+        let mut changed = 0;
+
+        for path in files {
+            let key = match self.key(path) {
+                Some(key) => key,
+                None => continue,
+            };
+            let (size, modified) = stamp(path).map_err(|e| MinigrepError::io(path, e))?;
+
+            let entry = match self.files.remove(&key) {
+                Some(entry) if entry.size == size && entry.modified == modified => entry,
+                _ => {
+                    changed += 1;
+                    let contents = fs::read(path).map_err(|e| MinigrepError::io(path, e))?;
+                    Entry {
+                        size,
+                        modified,
+                        trigrams: trigrams(&String::from_utf8_lossy(&contents)),
+                    }
+                }
+            };
+            current.insert(key, entry);
+        }
+
+        changed += self.files.len();
+        self.files = current;
+        Ok(changed)
+    }
+
+    /// Writes the index next to the files it covers, replacing the old one
+    /// in a single rename.
+    pub fn save(&self) -> Result<(), MinigrepError> {
+        let path = self.root.join(INDEX_FILE);
+        let temp = self.root.join(format!("{}.{}", INDEX_FILE, process::id()));
+
+        let result = File::create(&temp)
+            .and_then(|mut file| {
+                file.write_all(&encode(&self.files))?;
+                file.sync_all()
+            })
+            .and_then(|_| fs::rename(&temp, &path));
+        if result.is_err() {
+            let _ = fs::remove_file(&temp);
+        }
+        result.map_err(|e| MinigrepError::io(&path, e))
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Whether `path` could hold a match for `query`. Files the index does
+    /// not know always could.
+    pub fn may_match(&self, path: &Path, query: &Query) -> bool {
+        let entry = match self.key(path).and_then(|key| self.files.get(&key)) {
+            Some(entry) => entry,
+            None => return true,
+        };
+
+        match query {
+            Query::Anything => true,
+            Query::AnyOf(alternatives) => alternatives.iter().any(|required| {
+                required
+                    .iter()
+                    .all(|trigram| entry.trigrams.binary_search(trigram).is_ok())
+            }),
+        }
+    }
+
+    fn key(&self, path: &Path) -> Option<String> {
+        let relative = path.strip_prefix(&self.root).ok()?;
+        relative.to_str().map(|key| key.replace('\\', "/"))
+    }
+}
+
+/// Indexes the files under `root` for `minigrep index build`, reusing
+/// what an earlier index knew about unchanged files. Returns the number of
+/// files indexed and how many of those were added, changed or forgotten.
+pub fn build(root: &Path) -> Result<(usize, usize), MinigrepError> {
+    let files = walk::files(
+        &[root.to_string_lossy().into_owned()],
+        &Filter::default(),
+        |e| eprintln!("{}", e),
+    );
+    let mut index = Index::load(root)?;
+    let changed = index.update(&files)?;
+    index.save()?;
+    Ok((index.len(), changed))
+}
+
+/// Drops the files under indexed directories in `config.paths` that cannot
+/// match, updating each index first. An index that cannot be saved again
+/// is still used for this search, with a warning.
+pub fn narrow(config: &Config, mut files: Vec<PathBuf>) -> Result<Vec<PathBuf>, MinigrepError> {
+    let query = Query::new(config)?;
+
+    for root in config
+        .paths
+        .iter()
+        .map(Path::new)
+        .filter(|path| path.is_dir())
+    {
+        // The index covers every file, not just those `--glob` and
+        // `--type` let through this time. Anything that cannot be read was
+        // reported when the files to search were gathered.
+        let under = walk::files(
+            &[root.to_string_lossy().into_owned()],
+            &Filter::default(),
+            |_| {},
+        );
+
+        let mut index = Index::load(root)?;
+        if index.update(&under)? > 0 {
+            if let Err(e) = index.save() {
+                eprintln!("warning: could not update the index: {}", e);
+            }
+        }
+        files.retain(|path| !path.starts_with(root) || index.may_match(path, &query));
+    }
+
+    Ok(files)
+}
+
+/// The trigrams a file needs to hold a match.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Query {
+    /// Nothing can be ruled out.
+    Anything,
+    /// All the trigrams of at least one of these sets.
+    AnyOf(Vec<Vec<u32>>),
+}
+
+impl Query {
+    pub fn new(config: &Config) -> Result<Query, MinigrepError> {
+        // Modes that report on files without matches, or on lines that do
+        // not match, need every file. Fuzzy matches need not share any
+        // trigram with the query, and lines selected by `--query` need not
+        // hold any of its terms.
+        let reports_every_file = match config.output {
+            OutputMode::Lines | OutputMode::FilesWithMatches | OutputMode::Quiet => config.json,
+            OutputMode::Count | OutputMode::FilesWithoutMatch => true,
+        };
+        if reports_every_file
+            || config.invert_match
+            || config.fuzzy.is_some()
+            || config.expression.is_some()
+        {
+            return Ok(Query::Anything);
+        }
+
+        let mut patterns = config.patterns.clone();
+        for path in &config.pattern_files {
+            let contents =
+                fs::read_to_string(path).map_err(|e| MinigrepError::io(Path::new(path), e))?;
+            patterns.extend(contents.lines().map(String::from));
+        }
+        if config.patterns.is_empty() && config.pattern_files.is_empty() {
+            patterns.push(config.query.clone());
+        }
+
+        let mut alternatives = Vec::new();
+        for pattern in &patterns {
+            let literals = if config.regex {
+                match required_literals(pattern) {
+                    Some(literals) => literals,
+                    None => return Ok(Query::Anything),
+                }
+            } else {
+                vec![pattern.clone()]
+            };
+
+            let mut required: Vec<u32> = literals
+                .iter()
+                .flat_map(|literal| trigrams(literal))
+                .collect();
+            if required.is_empty() {
+                return Ok(Query::Anything);
+            }
+            required.sort_unstable();
+            required.dedup();
+            alternatives.push(required);
+        }
+
+        Ok(Query::AnyOf(alternatives))
+    }
+}
+
+// The runs of plain characters a regular expression cannot match without.
+// Returns None when that is too hard to tell, as with alternation.
+fn required_literals(pattern: &str) -> Option<Vec<String>> {
+    if pattern.contains('|') {
+        return None;
+    }
+
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut literals = Vec::new();
+    let mut run = String::new();
+    let mut depth = 0;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let (literal, next) = match chars[i] {
+            '\\' => match chars.get(i + 1) {
+                Some(&c) if !c.is_alphanumeric() => (Some(c), i + 2),
+                Some('x') => (None, i + 4),
+                _ => (None, i + 2),
+            },
+            '[' => (None, class_end(&chars, i)),
+            '(' => {
+                depth += 1;
+                (None, i + 1)
+            }
+            ')' => {
+                depth -= 1;
+                (None, i + 1)
+            }
+            // Counts of repetition, or the name in `\b{start-half}`.
+            '{' => match chars[i..].iter().position(|&c| c == '}') {
+                Some(end) => (None, i + end + 1),
+                None => (None, i + 1),
+            },
+            '.' | '^' | '$' | '?' | '*' | '+' | '}' => (None, i + 1),
+            c => (Some(c), i + 1),
+        };
+
+        // A character that may be repeated zero times is not required; one
+        // that may be repeated ends the run after it.
+        let quantifier = chars.get(next).cloned();
+        match literal {
+            Some(c) if depth == 0 && quantifier == Some('+') => {
+                run.push(c);
+                literals.push(std::mem::take(&mut run));
+            }
+            Some(c) if depth == 0 && !matches!(quantifier, Some('?' | '*' | '{')) => run.push(c),
+            _ => literals.push(std::mem::take(&mut run)),
+        }
+        i = next;
+    }
+
+    literals.push(run);
+    literals.retain(|literal| literal.len() >= 3);
+    Some(literals)
+}
+
+// The index just past the `]` closing the class that opens at `start`.
+fn class_end(chars: &[char], start: usize) -> usize {
+    let mut i = start + 1;
+    if chars.get(i) == Some(&'^') {
+        i += 1;
+    }
+    if chars.get(i) == Some(&']') {
+        i += 1;
+    }
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 2,
+            ']' => return i + 1,
+            _ => i += 1,
+        }
+    }
+    chars.len()
+}
+
+// The sorted, distinct ASCII trigrams of `text` once folded, leaving out
+// those that span lines since a match never does.
+fn trigrams(text: &str) -> Vec<u32> {
+    let folded = case::fold_str(text);
+    let mut trigrams: Vec<u32> = folded
+        .windows(3)
+        .filter(|window| window.iter().all(|&c| c.is_ascii() && c != '\n'))
+        .map(|window| window.iter().fold(0, |trigram, &c| trigram << 8 | c as u32))
+        .collect();
+    trigrams.sort_unstable();
+    trigrams.dedup();
+    trigrams
+}
+
+fn stamp(path: &Path) -> io::Result<(u64, (u64, u32))> {
+    let metadata = fs::metadata(path)?;
+    let modified = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    Ok((
+        metadata.len(),
+        (modified.as_secs(), modified.subsec_nanos()),
+    ))
+}
+
+fn encode(files: &BTreeMap<String, Entry>) -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend_from_slice(&(files.len() as u32).to_le_bytes());
+
+    for (path, entry) in files {
+        bytes.extend_from_slice(&(path.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(path.as_bytes());
+        bytes.extend_from_slice(&entry.size.to_le_bytes());
+        bytes.extend_from_slice(&entry.modified.0.to_le_bytes());
+        bytes.extend_from_slice(&entry.modified.1.to_le_bytes());
+        bytes.extend_from_slice(&(entry.trigrams.len() as u32).to_le_bytes());
+        for trigram in &entry.trigrams {
+            bytes.extend_from_slice(&trigram.to_le_bytes());
+        }
+    }
+
+    bytes
+}
+
+fn decode(bytes: &[u8]) -> Option<BTreeMap<String, Entry>> {
+    let mut reader = Reader {
+        bytes: bytes.strip_prefix(MAGIC)?,
+    };
+    let mut files = BTreeMap::new();
+
+    for _ in 0..reader.u32()? {
+        let length = reader.u32()? as usize;
+        let path = String::from_utf8(reader.take(length)?.to_vec()).ok()?;
+        let size = reader.u64()?;
+        let modified = (reader.u64()?, reader.u32()?);
+        let trigrams = (0..reader.u32()?)
+            .map(|_| reader.u32())
+            .collect::<Option<Vec<_>>>()?;
+
+        files.insert(
+            path,
+            Entry {
+                size,
+                modified,
+                trigrams,
+            },
+        );
+    }
+
+    Some(files)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.bytes.len() < n {
+            return None;
+        }
+        let (taken, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Some(taken)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.take(4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        let mut buf = [0; 8];
+        buf.copy_from_slice(self.take(8)?);
+        Some(u64::from_le_bytes(buf))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn config(args: &[&str]) -> Config {
+        let args: Vec<String> = std::iter::once("minigrep")
+            .chain(args.iter().cloned())
+            .map(String::from)
+            .collect();
+        Config::new(&args).unwrap()
+    }
+
+    fn trigram(s: &str) -> u32 {
+        trigrams(s)[0]
+    }
+
+    #[test]
+    fn extracts_trigrams() {
+        assert_eq!(vec![trigram("abc"), trigram("bcd")], trigrams("aBcD"));
+        assert_eq!(vec![trigram("ab\r")], trigrams("ab\r\ncd"));
+        assert_eq!(vec![trigram("ass"), trigram("mas")], trigrams("MAß"));
+        assert_eq!(vec![trigram("b c")], trigrams("éb cé"));
+    }
+
+    #[test]
+    fn finds_required_literals() {
+        assert_eq!(
+            Some(vec!["fn ".to_string(), " main".to_string()]),
+            required_literals(r"fn \w+ main")
+        );
+        assert_eq!(Some(vec!["colo".to_string()]), required_literals("colou?r"));
+        assert_eq!(
+            Some(vec!["a.b".to_string()]),
+            required_literals(r"a\.b[xyz]+(cde)")
+        );
+        assert_eq!(None, required_literals("foo|bar"));
+        assert_eq!(
+            Some(vec!["yyy".to_string()]),
+            required_literals("x{1,3}yyy")
+        );
+        assert_eq!(
+            Some(vec!["abcd".to_string()]),
+            required_literals(r"\x41bc\x42abcd")
+        );
+        assert_eq!(
+            Some(vec!["word".to_string()]),
+            required_literals(r"\b{start-half}word\b{end-half}")
+        );
+    }
+
+    #[test]
+    fn builds_queries() {
+        let query = Query::new(&config(&["-e", "hello", "-e", "world", "f"])).unwrap();
+        assert_eq!(
+            Query::AnyOf(vec![
+                vec![trigram("ell"), trigram("hel"), trigram("llo")],
+                vec![trigram("orl"), trigram("rld"), trigram("wor")],
+            ]),
+            query
+        );
+        assert_eq!(Query::Anything, Query::new(&config(&["hi", "f"])).unwrap());
+        assert_eq!(
+            Query::Anything,
+            Query::new(&config(&["-c", "hello", "f"])).unwrap()
+        );
+        assert_eq!(
+            Query::AnyOf(vec![vec![trigram("llo")]]),
+            Query::new(&config(&["-i", "HÉLLO", "f"])).unwrap()
+        );
+    }
+
+    #[test]
+    fn narrows_and_updates_incrementally() {
+        let root = env::temp_dir().join(format!("minigrep-index-{}", process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src/a.txt"), "needle here").unwrap();
+        fs::write(root.join("src/b.txt"), "only hay").unwrap();
+
+        assert_eq!((2, 2), build(&root).unwrap());
+        assert_eq!((2, 0), build(&root).unwrap());
+
+        let root_arg = root.to_string_lossy().into_owned();
+        let config = config(&["--index", "needle", &root_arg]);
+        let files = walk::files(&config.paths, &config.filter, |e| panic!("{}", e));
+        assert_eq!(
+            vec![root.join("src/a.txt")],
+            narrow(&config, files).unwrap()
+        );
+
+        // A file that changed is indexed again.
+        fs::write(root.join("src/b.txt"), "a needle too, now").unwrap();
+        let files = walk::files(&config.paths, &config.filter, |e| panic!("{}", e));
+        assert_eq!(2, narrow(&config, files).unwrap().len());
+        assert_eq!((2, 0), build(&root).unwrap());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,615 @@
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::ops::Range;
+use std::path::Path;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+use crate::case;
+use crate::color::{ColorChoice, Style};
+use crate::error::MinigrepError;
+use crate::matcher::{Match, Matcher, Searcher};
+use crate::Config;
+
+// Incremental search for `--interactive`: a full-screen list of the lines
+// of one file that match the query, redrawn as the query is typed. The
+// terminal is put in raw mode with `stty` and drawn on with plain ANSI
+// escape sequences, so any Unix terminal will do.
+//
+//   typing, Backspace, Ctrl-U     edit the query
+//   Up, Down, Ctrl-P, Ctrl-N      move the selection
+//   PageUp, PageDown, Home, End   move it further
+//   Tab                           toggle case sensitivity
+//   Enter                         open the selected line in $EDITOR
+//   Esc, Ctrl-C, Ctrl-D           quit
+
+const TTY: &str = "/dev/tty";
+
+// The query and status lines come before the results.
+const HEADER_ROWS: usize = 2;
+
+// How often to look for a change in the terminal's size while no key is
+// pressed.
+const RESIZE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Runs the interactive search on the one file in `config.paths`, and
+/// returns whether the query matched anything when it was left.
+pub fn run(config: &Config) -> Result<bool, MinigrepError> {
+    let path = Path::new(&config.paths[0]);
+    let read = || {
+        fs::read(path)
+            .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+            .map_err(|e| MinigrepError::io(path, e))
+    };
+    let tty_error = |e| MinigrepError::io(Path::new(TTY), e);
+
+    let mut contents = read()?;
+    let mut view = View::new(config);
+    let mut terminal = Terminal::open().map_err(tty_error)?;
+
+    loop {
+        let results = view.search(config, &contents);
+        let size = terminal.size();
+        let count = results.as_ref().map_or(0, Vec::len);
+        view.fit(count, size.0.saturating_sub(HEADER_ROWS));
+        terminal
+            .draw(&view.render(&results, path, size))
+            .map_err(tty_error)?;
+
+        // Waiting for a key, but redrawing if the terminal is resized.
+        // Asking for the size runs `stty`, so it is only done now and then.
+        let mut checked = Instant::now();
+        let key = loop {
+            if let Some(key) = terminal.read_key().map_err(tty_error)? {
+                break Some(key);
+            }
+            if checked.elapsed() >= RESIZE_CHECK_INTERVAL {
+                if terminal.size() != size {
+                    break None;
+                }
+                checked = Instant::now();
+            }
+        };
+        let key = match key {
+            Some(key) => key,
+            None => continue,
+        };
+
+        let page = size.0.saturating_sub(HEADER_ROWS).max(1);
+        match view.handle(key, count, page) {
+            Action::Redraw => {}
+            Action::Quit => return Ok(count > 0),
+            Action::Open => {
+                let line_number = match &results {
+                    Ok(results) => results[view.selected].line_number,
+                    Err(_) => continue,
+                };
+                terminal.suspend().map_err(tty_error)?;
+                let opened = open_in_editor(path, line_number, &terminal.tty);
+                terminal.resume().map_err(tty_error)?;
+                if let Err(e) = opened {
+                    view.message = Some(format!("could not run the editor: {}", e));
+                }
+                // The file may well have been changed.
+                contents = read()?;
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Key {
+    Char(char),
+    Backspace,
+    ClearQuery,
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    Tab,
+    Enter,
+    Escape,
+    Quit,
+    /// Anything else, which is ignored.
+    Other,
+}
+
+/// The key at the start of `bytes` and how many bytes it takes up, or None
+/// if `bytes` only holds the start of one.
+pub fn parse_key(bytes: &[u8]) -> Option<(Key, usize)> {
+    let key = match *bytes.first()? {
+        0x1b => return parse_escape(bytes),
+        b'\r' | b'\n' => Key::Enter,
+        b'\t' => Key::Tab,
+        0x7f | 0x08 => Key::Backspace,
+        0x03 | 0x04 => Key::Quit,
+        0x0e => Key::Down,
+        0x10 => Key::Up,
+        0x15 => Key::ClearQuery,
+        b if b < 0x20 => Key::Other,
+        b => {
+            let len = match b {
+                0xc0..=0xdf => 2,
+                0xe0..=0xef => 3,
+                0xf0..=0xf7 => 4,
+                _ => 1,
+            };
+            let bytes = bytes.get(..len)?;
+            let key = std::str::from_utf8(bytes)
+                .ok()
+                .and_then(|s| s.chars().next())
+                .map_or(Key::Other, Key::Char);
+            return Some((key, len));
+        }
+    };
+    Some((key, 1))
+}
+
+// Escape sequences are `ESC [` or `ESC O`, then any parameters, then a
+// final byte from `@` to `~`.
+fn parse_escape(bytes: &[u8]) -> Option<(Key, usize)> {
+    match bytes.get(1)? {
+        b'[' | b'O' => {
+            let end = 2 + bytes[2..].iter().position(|b| (0x40..=0x7e).contains(b))?;
+            let key = match &bytes[2..=end] {
+                b"A" => Key::Up,
+                b"B" => Key::Down,
+                b"5~" => Key::PageUp,
+                b"6~" => Key::PageDown,
+                b"H" | b"1~" | b"7~" => Key::Home,
+                b"F" | b"4~" | b"8~" => Key::End,
+                _ => Key::Other,
+            };
+            Some((key, end + 1))
+        }
+        _ => Some((Key::Escape, 1)),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Redraw,
+    Open,
+    Quit,
+}
+
+/// What the search looks like: the query and the selected result, along
+/// with the first result on screen.
+#[derive(Debug, Clone, PartialEq)]
+pub struct View {
+    pub query: String,
+    pub case_sensitive: bool,
+    /// Left as given until case sensitivity is toggled.
+    pub smart_case: bool,
+    pub selected: usize,
+    pub top: usize,
+    /// Shown in place of the status until the next key.
+    pub message: Option<String>,
+    matched: Style,
+}
+
+impl View {
+    pub fn new(config: &Config) -> View {
+        View {
+            query: String::new(),
+            case_sensitive: config.case_sensitive,
+            smart_case: config.smart_case,
+            selected: 0,
+            top: 0,
+            message: None,
+            matched: match config.color {
+                ColorChoice::Never => Style::default(),
+                _ => config.colors.matched,
+            },
+        }
+    }
+
+    /// The lines of `contents` that match the query, searched for the way
+    /// `config` says apart from case sensitivity.
+    pub fn search<'a>(&self, config: &Config, contents: &'a str) -> Result<Vec<Match<'a>>, String> {
+        let config = Config {
+            query: self.query.clone(),
+            case_sensitive: self.case_sensitive,
+            smart_case: self.smart_case,
+            regex: config.regex,
+            word_regexp: config.word_regexp,
+            line_regexp: config.line_regexp,
+            ..Config::default()
+        };
+        let matcher = Matcher::new(&config).map_err(|e| e.to_string())?;
+        Ok(Searcher::new(&matcher, contents).collect())
+    }
+
+    /// Applies `key`, given the number of results and how many fit on a
+    /// page.
+    pub fn handle(&mut self, key: Key, count: usize, page: usize) -> Action {
+        self.message = None;
+        let last = count.saturating_sub(1);
+        match key {
+            Key::Char(c) => self.edit(|query| query.push(c)),
+            Key::Backspace => self.edit(|query| {
+                query.pop();
+            }),
+            Key::ClearQuery => self.edit(String::clear),
+            Key::Up => self.selected = self.selected.saturating_sub(1),
+            Key::Down => self.selected = (self.selected + 1).min(last),
+            Key::PageUp => self.selected = self.selected.saturating_sub(page),
+            Key::PageDown => self.selected = (self.selected + page).min(last),
+            Key::Home => self.selected = 0,
+            Key::End => self.selected = last,
+            Key::Tab => {
+                // Smart case gives way to the opposite of what it chose.
+                let sensitive = if self.smart_case {
+                    case::has_uppercase(&self.query, false)
+                } else {
+                    self.case_sensitive
+                };
+                self.case_sensitive = !sensitive;
+                self.smart_case = false;
+                self.edit(|_| {});
+            }
+            Key::Enter if count > 0 => return Action::Open,
+            Key::Escape | Key::Quit => return Action::Quit,
+            Key::Enter | Key::Other => {}
+        }
+        Action::Redraw
+    }
+
+    // A new search starts back at the first result.
+    fn edit<F: FnOnce(&mut String)>(&mut self, f: F) {
+        f(&mut self.query);
+        self.selected = 0;
+        self.top = 0;
+    }
+
+    /// Keeps the selection among `count` results and scrolls so it is
+    /// within the `rows` on screen.
+    pub fn fit(&mut self, count: usize, rows: usize) {
+        self.selected = self.selected.min(count.saturating_sub(1));
+        if self.selected < self.top {
+            self.top = self.selected;
+        } else if rows > 0 && self.selected >= self.top + rows {
+            self.top = self.selected + 1 - rows;
+        }
+    }
+
+    /// Draws the whole screen, `size` being its rows and columns.
+    pub fn render(
+        &self,
+        results: &Result<Vec<Match>, String>,
+        path: &Path,
+        (rows, cols): (usize, usize),
+    ) -> String {
+        let mut screen = String::from("\x1b[?25l\x1b[H");
+        let query = format!("> {}", self.query);
+        screen.push_str(&fit_line(&query, &[], cols, Style::default()));
+        screen.push_str("\x1b[K");
+
+        let case = if self.smart_case {
+            "smart case"
+        } else if self.case_sensitive {
+            "case sensitive"
+        } else {
+            "ignoring case"
+        };
+        let status = match (&self.message, results) {
+            (Some(message), _) => message.clone(),
+            (None, Err(e)) => e.clone(),
+            (None, Ok(results)) => format!(
+                "{} matching lines in {} | {} | Tab: case  Enter: open  Esc: quit",
+                results.len(),
+                path.display(),
+                case
+            ),
+        };
+        let status = fit_line(&format!(" {}", status), &[], cols, Style::default());
+        let padding = cols.saturating_sub(status.chars().count());
+        screen.push_str(&format!(
+            "\r\n\x1b[7m{}{}\x1b[0m",
+            status,
+            " ".repeat(padding)
+        ));
+
+        let results = results.as_deref().unwrap_or(&[]);
+        let width = results
+            .last()
+            .map_or(1, |found| found.line_number.to_string().len());
+        let visible = results
+            .iter()
+            .enumerate()
+            .skip(self.top)
+            .take(rows.saturating_sub(HEADER_ROWS));
+        for (i, found) in visible {
+            let marker = if i == self.selected {
+                "\x1b[7m>\x1b[0m "
+            } else {
+                "  "
+            };
+            let prefix = format!("{:>width$}: ", found.line_number, width = width);
+            let room = cols.saturating_sub(2 + prefix.len());
+            screen.push_str(&format!(
+                "\r\n{}{}{}\x1b[K",
+                marker,
+                prefix,
+                fit_line(found.line, &found.ranges, room, self.matched)
+            ));
+        }
+
+        // Clears below the results, then puts the cursor after the query.
+        screen.push_str("\x1b[J");
+        let column = (query.chars().count() + 1).min(cols.max(1));
+        screen.push_str(&format!("\x1b[1;{}H\x1b[?25h", column));
+        screen
+    }
+}
+
+// Cuts `line` down to `width` characters, painting the byte `ranges` in it
+// with `style`. Control characters would move the cursor, so they are
+// shown as spaces.
+fn fit_line(line: &str, ranges: &[Range<usize>], width: usize, style: Style) -> String {
+    let mut pieces = Vec::new();
+    let mut last = 0;
+    for range in ranges {
+        if range.start >= last {
+            pieces.push((last..range.start, false));
+            pieces.push((range.clone(), true));
+            last = range.end;
+        }
+    }
+    pieces.push((last..line.len(), false));
+
+    let mut out = String::new();
+    let mut room = width;
+    for (range, matched) in pieces {
+        let text: String = line[range]
+            .chars()
+            .take(room)
+            .map(|c| if c.is_control() { ' ' } else { c })
+            .collect();
+        room -= text.chars().count();
+        if matched && !text.is_empty() {
+            out.push_str(&style.paint(&text));
+        } else {
+            out.push_str(&text);
+        }
+    }
+    out
+}
+
+// The controlling terminal, in raw mode and showing the alternate screen
+// for as long as this lives.
+struct Terminal {
+    tty: File,
+    // The settings to go back to, as printed by `stty -g`.
+    saved: String,
+    // Bytes read that do not yet make up a whole key.
+    pending: Vec<u8>,
+}
+
+impl Terminal {
+    fn open() -> io::Result<Terminal> {
+        let tty = OpenOptions::new().read(true).write(true).open(TTY)?;
+        let saved = stty(&tty, &["-g"])?.trim().to_string();
+        let mut terminal = Terminal {
+            tty,
+            saved,
+            pending: Vec::new(),
+        };
+        terminal.resume()?;
+        Ok(terminal)
+    }
+
+    // Reads wait at most a tenth of a second, so that a lone Esc can be
+    // told from the start of an escape sequence.
+    fn resume(&mut self) -> io::Result<()> {
+        stty(&self.tty, &["raw", "-echo", "min", "0", "time", "1"])?;
+        self.tty.write_all(b"\x1b[?1049h")
+    }
+
+    fn suspend(&mut self) -> io::Result<()> {
+        self.tty.write_all(b"\x1b[?1049l")?;
+        stty(&self.tty, &[&self.saved]).map(|_| ())
+    }
+
+    // Rows and columns, with a guess when the terminal will not say.
+    fn size(&self) -> (usize, usize) {
+        let size = stty(&self.tty, &["size"]).unwrap_or_default();
+        let mut numbers = size.split_whitespace().map(str::parse);
+        match (numbers.next(), numbers.next()) {
+            (Some(Ok(rows)), Some(Ok(cols))) if rows > 0 && cols > 0 => (rows, cols),
+            _ => (24, 80),
+        }
+    }
+
+    fn draw(&mut self, screen: &str) -> io::Result<()> {
+        self.tty.write_all(screen.as_bytes())?;
+        self.tty.flush()
+    }
+
+    // The next key, or None if none was pressed for a while.
+    fn read_key(&mut self) -> io::Result<Option<Key>> {
+        loop {
+            if let Some((key, len)) = parse_key(&self.pending) {
+                self.pending.drain(..len);
+                return Ok(Some(key));
+            }
+
+            let mut buf = [0; 64];
+            let read = match self.tty.read(&mut buf) {
+                Ok(read) => read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            if read > 0 {
+                self.pending.extend_from_slice(&buf[..read]);
+                continue;
+            }
+
+            // Nothing more came, so what is pending will not be completed.
+            return Ok(match self.pending.first() {
+                None => None,
+                Some(0x1b) => {
+                    self.pending.remove(0);
+                    Some(Key::Escape)
+                }
+                Some(_) => {
+                    self.pending.clear();
+                    Some(Key::Other)
+                }
+            });
+        }
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = self.suspend();
+    }
+}
+
+// Runs `stty` on the terminal and returns what it printed.
+fn stty(tty: &File, args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(tty.try_clone()?)
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "stty {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+// Opens `path` at `line_number` in `$VISUAL` or `$EDITOR`, which may hold
+// arguments of its own, or in vi. Most editors take the line as `+N`.
+fn open_in_editor(path: &Path, line_number: usize, tty: &File) -> io::Result<()> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_default();
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or("vi");
+
+    let status = Command::new(program)
+        .args(words)
+        .arg(format!("+{}", line_number))
+        .arg(path)
+        .stdin(tty.try_clone()?)
+        .stdout(tty.try_clone()?)
+        .stderr(tty.try_clone()?)
+        .status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "{} exited with {}",
+            program, status
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_keys() {
+        assert_eq!(Some((Key::Char('a'), 1)), parse_key(b"ab"));
+        assert_eq!(Some((Key::Char('é'), 2)), parse_key("é".as_bytes()));
+        assert_eq!(None, parse_key(&"é".as_bytes()[..1]));
+        assert_eq!(Some((Key::Up, 3)), parse_key(b"\x1b[A"));
+        assert_eq!(Some((Key::Down, 3)), parse_key(b"\x1bOB"));
+        assert_eq!(Some((Key::PageDown, 4)), parse_key(b"\x1b[6~x"));
+        assert_eq!(Some((Key::Other, 6)), parse_key(b"\x1b[1;5C"));
+        assert_eq!(None, parse_key(b"\x1b"));
+        assert_eq!(None, parse_key(b"\x1b[5"));
+        assert_eq!(Some((Key::Escape, 1)), parse_key(b"\x1bx"));
+        assert_eq!(Some((Key::Backspace, 1)), parse_key(b"\x7f"));
+        assert_eq!(Some((Key::Quit, 1)), parse_key(b"\x03"));
+    }
+
+    #[test]
+    fn edits_moves_and_toggles_case() {
+        let config = Config::default();
+        let contents = "Rust\nrust\ntrust\nbust\n";
+        let mut view = View::new(&config);
+        let lines = |view: &View| -> Vec<usize> {
+            let results = view.search(&config, contents).unwrap();
+            results.iter().map(|found| found.line_number).collect()
+        };
+
+        for c in "rust".chars() {
+            view.handle(Key::Char(c), 0, 10);
+        }
+        assert_eq!(vec![2, 3], lines(&view));
+        assert_eq!(Action::Redraw, view.handle(Key::Tab, 2, 10));
+        assert!(!view.case_sensitive);
+        assert_eq!(vec![1, 2, 3], lines(&view));
+
+        view.handle(Key::Down, 3, 10);
+        view.handle(Key::Down, 3, 10);
+        view.handle(Key::Down, 3, 10);
+        assert_eq!(2, view.selected);
+        view.handle(Key::PageUp, 3, 10);
+        assert_eq!(0, view.selected);
+        view.handle(Key::End, 3, 10);
+        assert_eq!(Action::Open, view.handle(Key::Enter, 3, 10));
+
+        view.handle(Key::Backspace, 3, 10);
+        assert_eq!(("rus", 0), (view.query.as_str(), view.selected));
+        view.handle(Key::ClearQuery, 3, 10);
+        assert_eq!(4, lines(&view).len());
+        assert_eq!(Action::Quit, view.handle(Key::Escape, 4, 10));
+    }
+
+    #[test]
+    fn scrolls_to_the_selection() {
+        let mut view = View::new(&Config::default());
+        view.handle(Key::PageDown, 100, 10);
+        view.handle(Key::PageDown, 100, 10);
+        view.fit(100, 10);
+        assert_eq!((20, 11), (view.selected, view.top));
+        view.handle(Key::Home, 100, 10);
+        view.fit(100, 10);
+        assert_eq!((0, 0), (view.selected, view.top));
+        view.handle(Key::End, 100, 10);
+        view.fit(5, 10);
+        assert_eq!((4, 0), (view.selected, view.top));
+    }
+
+    #[test]
+    fn renders_matches_within_the_screen() {
+        let style = Style {
+            color: None,
+            bold: true,
+        };
+        let ranges = [0..1, 2..3];
+        assert_eq!(
+            "\u{1b}[1ma\u{1b}[0mb\u{1b}[1mc\u{1b}[0m d",
+            fit_line("abc\td", &ranges, 10, style)
+        );
+        assert_eq!(
+            "\u{1b}[1ma\u{1b}[0mb",
+            fit_line("abc\td", &ranges, 2, style)
+        );
+
+        let config = Config {
+            color: ColorChoice::Never,
+            ..Config::default()
+        };
+        let mut view = View::new(&config);
+        view.query = String::from("o");
+        let results = view.search(&config, "one\ntwo\nthree\nfour\n");
+        let screen = view.render(&results, Path::new("f.txt"), (4, 40));
+        assert!(screen.contains("> o"));
+        assert!(screen.contains(" 3 matching lines in f.txt"));
+        assert!(screen.contains("\x1b[7m>\x1b[0m 1: one"));
+        assert!(screen.contains("  2: two"));
+        assert!(!screen.contains("four"));
+    }
+}
@@ -0,0 +1,221 @@
+use std::fmt;
+use std::ops::Range;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::matcher::Match;
+
+// A minimal JSON value, enough to write the `--json` event stream. Every
+// event is printed on its own line (JSON Lines):
+//
+//   {"type":"begin","data":{"path":"src/lib.rs"}}
+//   {"type":"match","data":{"path":..,"line_number":..,"byte_offset":..,
+//                           "line":..,"submatches":[{"text":..,"start":..,"end":..}],
+//                           "distance":.. (with --fuzzy only)}}
+//   {"type":"context","data":{"path":..,"line_number":..,"byte_offset":..,"line":..}}
+//   {"type":"end","data":{"path":..,"matched_lines":..}}
+//   {"type":"summary","data":{"elapsed_secs":..,"searches":..,
+//                             "searches_with_match":..,"matched_lines":..}}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    pub fn object(fields: Vec<(&str, Value)>) -> Value {
+        Value::Object(
+            fields
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        )
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Value {
+        Value::String(s.to_string())
+    }
+}
+
+impl From<usize> for Value {
+    fn from(n: usize) -> Value {
+        Value::Number(n as f64)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Value {
+        Value::Bool(b)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Null => write!(f, "null"),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Number(n) => write!(f, "{}", n),
+            Value::String(s) => write_string(f, s),
+            Value::Array(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+            Value::Object(fields) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+fn event(kind: &str, data: Vec<(&str, Value)>) -> Value {
+    Value::object(vec![("type", kind.into()), ("data", Value::object(data))])
+}
+
+fn path_value(path: &Path) -> Value {
+    Value::String(path.display().to_string())
+}
+
+pub fn begin(path: &Path) -> Value {
+    event("begin", vec![("path", path_value(path))])
+}
+
+/// A match event. `distance` is the edit distance of the line from the
+/// query with `--fuzzy`.
+pub fn matched(path: &Path, found: &Match, distance: Option<usize>) -> Value {
+    let submatches = found
+        .ranges
+        .iter()
+        .map(|range: &Range<usize>| {
+            Value::object(vec![
+                ("text", found.line[range.clone()].into()),
+                ("start", range.start.into()),
+                ("end", range.end.into()),
+            ])
+        })
+        .collect();
+
+    let mut data = vec![
+        ("path", path_value(path)),
+        ("line_number", found.line_number.into()),
+        ("byte_offset", found.byte_offset.into()),
+        ("line", found.line.into()),
+        ("submatches", Value::Array(submatches)),
+    ];
+    if let Some(distance) = distance {
+        data.push(("distance", distance.into()));
+    }
+    event("match", data)
+}
+
+pub fn context(path: &Path, line_number: usize, byte_offset: usize, line: &str) -> Value {
+    event(
+        "context",
+        vec![
+            ("path", path_value(path)),
+            ("line_number", line_number.into()),
+            ("byte_offset", byte_offset.into()),
+            ("line", line.into()),
+        ],
+    )
+}
+
+pub fn end(path: &Path, matched_lines: usize) -> Value {
+    event(
+        "end",
+        vec![
+            ("path", path_value(path)),
+            ("matched_lines", matched_lines.into()),
+        ],
+    )
+}
+
+pub fn summary(
+    elapsed: Duration,
+    searches: usize,
+    searches_with_match: usize,
+    matched_lines: usize,
+) -> Value {
+    event(
+        "summary",
+        vec![
+            ("elapsed_secs", Value::Number(elapsed.as_secs_f64())),
+            ("searches", searches.into()),
+            ("searches_with_match", searches_with_match.into()),
+            ("matched_lines", matched_lines.into()),
+        ],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_strings() {
+        let value = Value::from("say \"hi\"\\\n\t\u{1}é");
+        assert_eq!(r#""say \"hi\"\\\n\t\u0001é""#, value.to_string());
+    }
+
+    #[test]
+    fn writes_nested_values() {
+        let value = Value::object(vec![
+            (
+                "a",
+                Value::Array(vec![1usize.into(), Value::Null, true.into()]),
+            ),
+            ("b", Value::Number(0.5)),
+        ]);
+        assert_eq!(r#"{"a":[1,null,true],"b":0.5}"#, value.to_string());
+    }
+
+    #[test]
+    fn match_event() {
+        let found = Match {
+            line_number: 2,
+            byte_offset: 10,
+            line: "a foo",
+            ranges: vec![Range { start: 2, end: 5 }],
+        };
+        assert_eq!(
+            r#"{"type":"match","data":{"path":"x.txt","line_number":2,"byte_offset":10,"line":"a foo","submatches":[{"text":"foo","start":2,"end":5}]}}"#,
+            matched(Path::new("x.txt"), &found, None).to_string()
+        );
+    }
+}
@@ -1,43 +1,77 @@
-use std::error::Error;
-use std::fs;
-use std::env;
-
-pub struct Config {
-    pub query: String,
-    pub filename: String,
-    pub case_sensitive: bool,
-}
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
 
-impl Config {
-    pub fn new(args: &[String]) -> Result<Config, &str> {
-        if args.len() < 3 {
-            return Err("not enought arguments");
-        }
+pub mod aho_corasick;
+pub mod case;
+pub mod color;
+mod config;
+pub mod context;
+pub mod error;
+pub mod expr;
+pub mod follow;
+pub mod fuzzy;
+pub mod glob;
+pub mod index;
+pub mod interactive;
+pub mod json;
+pub mod matcher;
+pub mod memmem;
+pub mod output;
+pub mod parallel;
+pub mod printer;
+pub mod regex;
+pub mod replace;
+pub mod stream;
+pub mod types;
+pub mod walk;
 
-        let query = args[1].clone();
-        let filename = args[2].clone();
+use context::ContextLine;
+use error::MinigrepError;
+use follow::Follower;
+use matcher::Matcher;
+use memmem::Finder;
+use output::{FileOutput, Output};
+use printer::Printer;
+use regex::Regex;
+use replace::Replacer;
+use stream::StreamSearcher;
 
-        let case_sensitive = if args.len() == 4 {
-            args[3].clone().parse().unwrap()
-        } else {
-            env::var("CASE_INSENSITIVE").is_err()
-        };
+pub use config::{usage, Config, ConfigError, OutputMode};
 
-        Ok(Config {
-            query,
-            filename,
-            case_sensitive,
-        })
-    }
-}
+/// The path that stands for standard input.
+pub const STDIN_PATH: &str = "-";
 
+// How standard input is named in output.
+const STDIN_LABEL: &str = "(standard input)";
+
+/// Finds the lines of `contents` that contain `query`. Rather than check
+/// each line in turn, this looks for the query in all of `contents` at
+/// once and only then works out which line each occurrence is on.
 pub fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+    if query.is_empty() {
+        return contents.lines().collect();
+    }
+
+    let finder = Finder::new(query.as_bytes());
+    let bytes = contents.as_bytes();
     let mut results = Vec::new();
+    let mut start = 0;
 
-    for line in contents.lines() {
-        if line.contains(query) {
-           results.push(line); 
+    while let Some(found) = finder.find_at(bytes, start) {
+        let line_start = bytes[..found].iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+        let line_end = memmem::memchr(b'\n', &bytes[found..]).map_or(bytes.len(), |i| found + i);
+        let mut line = &contents[line_start..line_end];
+        if line_end < bytes.len() {
+            line = line.strip_suffix('\r').unwrap_or(line);
         }
+        // An occurrence running past the end of its line is not in it.
+        if found + query.len() <= line_start + line.len() {
+            results.push(line);
+        }
+        start = line_end + 1;
     }
 
     results
@@ -47,11 +81,11 @@ pub fn search_case_insensitive<'a>(
     query: &str,
     contents: &'a str
 ) -> Vec<&'a str> {
-    let query = query.to_lowercase();
+    let matcher = Matcher::case_insensitive(query);
     let mut results = Vec::new();
 
     for line in contents.lines() {
-        if line.to_lowercase().contains(&query) {
+        if matcher.is_match(line) {
            results.push(line); 
         }
     }
@@ -59,20 +93,312 @@ pub fn search_case_insensitive<'a>(
     results
 }
 
-pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    let contents = fs::read_to_string(config.filename)?;
+pub fn search_regex<'a>(regex: &Regex, contents: &'a str) -> Vec<&'a str> {
+    let mut results = Vec::new();
+
+    for line in contents.lines() {
+        if regex.is_match(line) {
+           results.push(line); 
+        }
+    }
+
+    results
+}
 
-    let results = if config.case_sensitive {
-        search(&config.query, &contents)
+/// Searches every file named by `config` and returns whether any line was
+/// selected. When several files are searched, those that fail are reported
+/// on standard error and the rest are still searched.
+pub fn run(config: Config) -> Result<bool, MinigrepError> {
+    if config.interactive {
+        return interactive::run(&config);
+    }
+
+    let started = Instant::now();
+    let matcher = Matcher::new(&config)?;
+
+    let mut unreadable = Vec::new();
+    let mut files = walk::files(&config.paths, &config.filter, |e| unreadable.push(e));
+    // With nothing else to search, a path that cannot be read is the error.
+    if files.is_empty() && unreadable.len() == 1 {
+        return Err(unreadable.remove(0));
+    }
+    let mut failed = unreadable.len();
+    for e in unreadable {
+        eprintln!("{}", e);
+    }
+    if config.index {
+        files = index::narrow(&config, files)?;
+    }
+    let show_path = files.len() > 1
+        || config.paths.len() > 1
+        || config.paths.iter().any(|path| Path::new(path).is_dir());
+    if config.sort_by_path {
+        files.sort();
+    }
+
+    let mut searcher = StreamSearcher::new(&matcher);
+    searcher.invert_match = config.invert_match;
+    searcher.max_count = config.max_count;
+    searcher.text = config.text;
+    searcher.multiline = config.multiline;
+    match config.output {
+        OutputMode::Lines => {
+            searcher.before_context = config.before_context;
+            searcher.after_context = config.after_context;
+        }
+        OutputMode::Count => {}
+        // One selected line is enough to know the answer.
+        _ => searcher.max_count = Some(config.max_count.unwrap_or(1).min(1)),
+    }
+
+    // Followed files never end, so each needs a thread of its own.
+    let threads = if config.follow {
+        files.len()
+    } else if config.threads == 0 {
+        parallel::default_threads()
     } else {
-        search_case_insensitive(&config.query, &contents)
+        config.threads
+    };
+
+    // A single input is written out line by line, as is standard input,
+    // so minigrep works at the end of a pipe that stays open. Followed
+    // files never end, so their lines are written as they come whatever
+    // else is being searched.
+    let output = Output::new(config.sort_by_path);
+    let single_input = files.len() == 1;
+    let chunk_size = |path: &Path| {
+        if single_input || config.follow || path.as_os_str() == STDIN_PATH {
+            0
+        } else {
+            output::CHUNK_SIZE
+        }
     };
 
-    for line in results {
-        println!("{}", line);
+    let printer = Printer::new(&config);
+    let replacer = config
+        .replace
+        .as_ref()
+        .map(|replacement| Replacer::new(replacement, &matcher));
+    let found_any = AtomicBool::new(false);
+    let stdout_closed = AtomicBool::new(false);
+    let mut failure = None;
+    let mut searches = 0;
+    let mut searches_with_match = 0;
+    let mut matched_lines = 0;
+    parallel::for_each(
+        &files,
+        threads,
+        config.sort_by_path,
+        |index, path| {
+            let mut out = output.file(index, chunk_size(path), !config.follow);
+            let quiet_and_done =
+                config.output == OutputMode::Quiet && found_any.load(Ordering::SeqCst);
+            if quiet_and_done || stdout_closed.load(Ordering::SeqCst) {
+                return Ok(0);
+            }
+            let label = if path.as_os_str() == STDIN_PATH {
+                Path::new(STDIN_LABEL)
+            } else {
+                path.as_path()
+            };
+            let display_path = if show_path { Some(label) } else { None };
+            let result = match &replacer {
+                Some(replacer) if config.dry_run || config.in_place => {
+                    replace_file(&config, replacer, path, &mut out)
+                }
+                _ => search_file(
+                    &config,
+                    &searcher,
+                    &printer,
+                    replacer.as_ref(),
+                    path,
+                    label,
+                    display_path,
+                    &mut out,
+                ),
+            }
+            .and_then(|count| out.finish().map(|_| count));
+            match &result {
+                Ok(count) if *count > 0 => found_any.store(true, Ordering::SeqCst),
+                // Whatever was reading the output has gone away, as after
+                // `| head`, once it had seen something.
+                Err(e) if e.kind() == io::ErrorKind::BrokenPipe => {
+                    found_any.store(true, Ordering::SeqCst);
+                    stdout_closed.store(true, Ordering::SeqCst);
+                }
+                _ => {}
+            }
+            result.map_err(|e| MinigrepError::io(label, e))
+        },
+        |result: Result<usize, MinigrepError>| match result {
+            Ok(count) => {
+                searches += 1;
+                if count > 0 {
+                    searches_with_match += 1;
+                }
+                matched_lines += count;
+            }
+            // Not worth reporting, see above.
+            Err(MinigrepError::Io { source, .. }) if source.kind() == io::ErrorKind::BrokenPipe => {
+            }
+            Err(e) if show_path => {
+                failed += 1;
+                eprintln!("{}", e);
+            }
+            Err(e) => failure = Some(e),
+        },
+    );
+
+    if config.json {
+        let summary = json::summary(
+            started.elapsed(),
+            searches,
+            searches_with_match,
+            matched_lines,
+        );
+        // Ignored like the results above, so a closed pipe is not a panic.
+        let _ = writeln!(io::stdout(), "{}", summary);
     }
 
-    Ok(())
+    let found_any = found_any.into_inner();
+    match failure {
+        Some(e) => Err(e),
+        // Like grep, a match found in quiet mode outweighs any error.
+        None if found_any && config.output == OutputMode::Quiet => Ok(true),
+        None if failed > 0 => Err(MinigrepError::Incomplete { failed }),
+        None => Ok(found_any),
+    }
+}
+
+// Searches a single file, or standard input, writes what it finds to `out`
+// and returns the number of selected lines. `label` names the file in
+// output.
+#[allow(clippy::too_many_arguments)]
+fn search_file(
+    config: &Config,
+    searcher: &StreamSearcher,
+    printer: &Printer,
+    replacer: Option<&Replacer>,
+    path: &Path,
+    label: &Path,
+    display_path: Option<&Path>,
+    output: &mut FileOutput,
+) -> io::Result<usize> {
+    let reader: Box<dyn BufRead> = if path.as_os_str() == STDIN_PATH {
+        Box::new(io::stdin().lock())
+    } else if config.follow {
+        Box::new(BufReader::new(Follower::open(path, follow::POLL_INTERVAL)?))
+    } else {
+        Box::new(BufReader::new(File::open(path)?))
+    };
+    if config.json {
+        writeln!(output, "{}", json::begin(label))?;
+    }
+
+    let outcome = searcher.search(reader, |line| {
+        if config.output != OutputMode::Lines {
+            return Ok(());
+        }
+        if config.json {
+            match line {
+                ContextLine::Separator => {}
+                ContextLine::Match(found) => {
+                    let distance = searcher.matcher().distance(found.line);
+                    writeln!(output, "{}", json::matched(label, &found, distance))?
+                }
+                ContextLine::Context {
+                    line_number,
+                    byte_offset,
+                    line,
+                } => writeln!(
+                    output,
+                    "{}",
+                    json::context(label, line_number, byte_offset, line)
+                )?,
+            }
+        } else {
+            match line {
+                ContextLine::Separator => printer.write_context_break(output)?,
+                ContextLine::Match(found) => {
+                    let replaced =
+                        replacer.map(|replacer| replacer.replace(found.line, &found.ranges));
+                    let (line, ranges) = match &replaced {
+                        Some((line, ranges)) => (line.as_str(), ranges.as_slice()),
+                        None => (found.line, found.ranges.as_slice()),
+                    };
+                    printer.write_line(
+                        output,
+                        display_path,
+                        found.line_number,
+                        found.byte_offset,
+                        line,
+                        ranges,
+                        searcher.matcher().distance(found.line),
+                        ':',
+                    )?
+                }
+                ContextLine::Context {
+                    line_number,
+                    byte_offset,
+                    line,
+                } => printer.write_line(
+                    output,
+                    display_path,
+                    line_number,
+                    byte_offset,
+                    line,
+                    &[],
+                    None,
+                    '-',
+                )?,
+            }
+        }
+
+        output.end_line()
+    })?;
+
+    if config.json {
+        writeln!(output, "{}", json::end(label, outcome.count))?;
+        return Ok(outcome.count);
+    }
+
+    let count = outcome.count;
+    match config.output {
+        OutputMode::Lines if outcome.binary_match => {
+            printer.write_binary_match(output, label)?
+        }
+        OutputMode::Count => printer.write_count(output, display_path, count)?,
+        OutputMode::FilesWithMatches if count > 0 => printer.write_path_line(output, label)?,
+        OutputMode::FilesWithoutMatch if count == 0 => printer.write_path_line(output, label)?,
+        _ => {}
+    }
+
+    Ok(count)
+}
+
+// Applies `--replace` to a whole file, writing the changes to `output` as
+// a diff for `--dry-run` or back to the file for `--in-place`. Returns the
+// number of lines changed.
+fn replace_file(
+    config: &Config,
+    replacer: &Replacer,
+    path: &Path,
+    output: &mut FileOutput,
+) -> io::Result<usize> {
+    let contents = fs::read_to_string(path)?;
+    if !config.text && contents.contains('\0') {
+        return Ok(0);
+    }
+
+    let rewrite = replacer.rewrite(&contents);
+    let changed = rewrite.changed_lines();
+    if config.dry_run {
+        rewrite.write_diff(output, path)?;
+    } else if changed > 0 {
+        replace::write_atomically(path, rewrite.contents().as_bytes())?;
+    }
+
+    Ok(changed)
 }
 
 #[cfg(test)]
@@ -91,6 +417,19 @@ Duct tape.";
         assert_eq!(vec!["safe, fast, productive."], search(query, contents));
     }
 
+    #[test]
+    fn search_reports_each_line_once() {
+        let contents = "to be\r\nor not to be\nthat is\nthe question: to";
+
+        assert_eq!(
+            vec!["to be", "or not to be", "the question: to"],
+            search("to", contents)
+        );
+        assert_eq!(Vec::<&str>::new(), search("be\r", contents));
+        assert_eq!(Vec::<&str>::new(), search("be\nthat", contents));
+        assert_eq!(4, search("", contents).len());
+    }
+
     #[test]
     fn case_insensitive() {
         let query = "rUsT";
@@ -105,4 +444,19 @@ Trust me.";
             search_case_insensitive(query, contents)
         );
     }
+
+    #[test]
+    fn regex_search() {
+        let regex = Regex::new(r"^\w+:$|t(hree|ape)").unwrap();
+        let contents = "\
+Rust:
+safe, fast, productive.
+Pick three.
+Duct tape.";
+
+        assert_eq!(
+            vec!["Rust:", "Pick three.", "Duct tape."],
+            search_regex(&regex, contents)
+        );
+    }
 }
@@ -1,18 +1,68 @@
 use std::env;
+use std::io::{self, Write};
+use std::path::Path;
 use std::process;
 
-use minigrep::Config;
+use minigrep::{Config, ConfigError};
+
+// Exit statuses, as for grep: 0 when a line was selected, 1 when none
+// was, and 2 when something went wrong.
+const MATCHED: i32 = 0;
+const NO_MATCH: i32 = 1;
+const FAILED: i32 = 2;
 
 fn main() {
     let args: Vec<String> = env::args().collect();
 
-    let config = Config::new(&args).unwrap_or_else(|err| {
-        eprintln!("Problem parsing arguments: {}", err);
-        process::exit(1);
+    // Anything else starting with `index` is a search for the word.
+    if args.get(1).map(String::as_str) == Some("index")
+        && args.get(2).map(String::as_str) == Some("build")
+    {
+        index_build(&args[3..]);
+    }
+
+    let config = Config::new(&args).unwrap_or_else(|err| match err {
+        ConfigError::Help | ConfigError::Version => {
+            let _ = writeln!(io::stdout(), "{}", err);
+            process::exit(0);
+        }
+        _ => {
+            eprintln!("Problem parsing arguments: {}", err);
+            eprintln!("Try 'minigrep --help' for more information.");
+            process::exit(FAILED);
+        }
     });
 
-    if let Err(e) = minigrep::run(config) {
-        eprintln!("Application error: {}", e);
-        process::exit(1);
+    match minigrep::run(config) {
+        Ok(true) => process::exit(MATCHED),
+        Ok(false) => process::exit(NO_MATCH),
+        Err(e) => {
+            eprintln!("Application error: {}", e);
+            process::exit(FAILED);
+        }
+    };
+}
+
+// `minigrep index build [DIR]`, given what comes after `build`.
+fn index_build(args: &[String]) -> ! {
+    let dir = match args {
+        [] => ".",
+        [dir] => dir,
+        _ => {
+            eprintln!("Problem parsing arguments: expected 'index build [DIR]'");
+            eprintln!("Try 'minigrep --help' for more information.");
+            process::exit(FAILED);
+        }
     };
+
+    match minigrep::index::build(Path::new(dir)) {
+        Ok((files, changed)) => {
+            let _ = writeln!(io::stdout(), "Indexed {} files, {} changed", files, changed);
+            process::exit(0);
+        }
+        Err(e) => {
+            eprintln!("Application error: {}", e);
+            process::exit(FAILED);
+        }
+    }
 }
@@ -0,0 +1,577 @@
+use std::cmp::Reverse;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+
+use crate::aho_corasick::AhoCorasick;
+use crate::case;
+use crate::error::MinigrepError;
+use crate::expr::Expr;
+use crate::fuzzy::Fuzzy;
+use crate::memmem::Finder;
+use crate::regex::{self, Regex};
+use crate::Config;
+
+/// Finds occurrences of the query inside a single line.
+#[derive(Debug, Clone)]
+pub enum Matcher {
+    Literal(Finder),
+    CaseInsensitive(Vec<char>),
+    Regex(Regex),
+    Multiple(AhoCorasick),
+    Fuzzy(Fuzzy),
+    /// Only occurrences bounded by non-word characters, for `-w`.
+    Word(Box<Matcher>),
+    /// Only occurrences spanning whole lines, for `-x`.
+    Line(Box<Matcher>),
+    /// Lines for which a boolean expression over patterns holds, for
+    /// `--query`.
+    Expression(Box<Expr<Matcher>>),
+}
+
+impl Matcher {
+    pub fn new(config: &Config) -> Result<Matcher, MinigrepError> {
+        if let Some(expression) = &config.expression {
+            // Each term is a pattern of its own, so `-w` and `-x` apply to
+            // each rather than to the expression.
+            let expression = expression.try_map(&mut |term: &String| {
+                Ok::<_, MinigrepError>(Matcher::bounded(config, Matcher::single(config, term)?))
+            })?;
+            return Ok(Matcher::Expression(Box::new(expression)));
+        }
+
+        let matcher = if !config.patterns.is_empty() || !config.pattern_files.is_empty() {
+            Matcher::multiple(config)?
+        } else {
+            Matcher::single(config, &config.query)?
+        };
+
+        Ok(Matcher::bounded(config, matcher))
+    }
+
+    // Applies `-x` or `-w`.
+    fn bounded(config: &Config, matcher: Matcher) -> Matcher {
+        if config.line_regexp {
+            Matcher::Line(Box::new(matcher))
+        } else if config.word_regexp {
+            Matcher::Word(Box::new(matcher))
+        } else {
+            matcher
+        }
+    }
+
+    fn single(config: &Config, pattern: &str) -> Result<Matcher, MinigrepError> {
+        let case_sensitive = case_sensitive(config, [pattern]);
+        Ok(if let Some(max_distance) = config.fuzzy {
+            Matcher::Fuzzy(Fuzzy::new(pattern, max_distance, !case_sensitive))
+        } else if config.regex {
+            Matcher::Regex(compile(pattern, case_sensitive, Bounds::of(config), None)?)
+        } else if case_sensitive {
+            Matcher::literal(pattern)
+        } else {
+            Matcher::case_insensitive(pattern)
+        })
+    }
+
+    // Patterns given with `-e` and read from `-f` files, one per line.
+    fn multiple(config: &Config) -> Result<Matcher, MinigrepError> {
+        let mut patterns: Vec<(Option<&Path>, String)> = config
+            .patterns
+            .iter()
+            .map(|pattern| (None, pattern.clone()))
+            .collect();
+        for path in &config.pattern_files {
+            let path = Path::new(path);
+            let contents =
+                fs::read_to_string(path).map_err(|e| MinigrepError::io(path, e))?;
+            patterns.extend(contents.lines().map(|line| (Some(path), line.to_string())));
+        }
+
+        let case_sensitive = case_sensitive(config, patterns.iter().map(|(_, pattern)| pattern));
+        if !config.regex {
+            return Ok(Matcher::Multiple(AhoCorasick::new(
+                patterns.iter().map(|(_, pattern)| pattern),
+                !case_sensitive,
+            )));
+        }
+
+        // Compiling each pattern on its own first points errors at the
+        // pattern, and the file, that caused them.
+        for (path, pattern) in &patterns {
+            compile(pattern, case_sensitive, Bounds::None, *path)?;
+        }
+
+        let alternation = if patterns.is_empty() {
+            // Matches nothing, like an empty pattern file does for grep.
+            String::from("[^\\s\\S]")
+        } else {
+            patterns
+                .iter()
+                .map(|(_, pattern)| format!("(?:{})", pattern))
+                .collect::<Vec<_>>()
+                .join("|")
+        };
+
+        Ok(Matcher::Regex(compile(
+            &alternation,
+            case_sensitive,
+            Bounds::of(config),
+            None,
+        )?))
+    }
+
+    pub fn literal(query: &str) -> Matcher {
+        Matcher::Literal(Finder::new(query.as_bytes()))
+    }
+
+    pub fn case_insensitive(query: &str) -> Matcher {
+        Matcher::CaseInsensitive(case::fold_str(query))
+    }
+
+    /// The regular expression behind this matcher, if there is one.
+    pub fn regex(&self) -> Option<&Regex> {
+        match self {
+            Matcher::Regex(regex) => Some(regex),
+            Matcher::Word(inner) | Matcher::Line(inner) => inner.regex(),
+            _ => None,
+        }
+    }
+
+    /// A finder for bytes that every matching line contains, so that input
+    /// can be searched a block at a time and only the lines with an
+    /// occurrence looked at more closely. A needle with a line ending or a
+    /// replacement character could be split or made up by the reading of
+    /// lines, so it does not count.
+    pub fn required_finder(&self) -> Option<&Finder> {
+        match self {
+            Matcher::Literal(finder) => {
+                let needle = finder.needle();
+                let usable = !needle.is_empty()
+                    && !needle.contains(&b'\n')
+                    && !String::from_utf8_lossy(needle).contains('\u{FFFD}');
+                Some(finder).filter(|_| usable)
+            }
+            Matcher::Word(inner) | Matcher::Line(inner) => inner.required_finder(),
+            _ => None,
+        }
+    }
+
+    /// The edit distance of the closest part of `line` to the query, for
+    /// fuzzy matchers.
+    pub fn distance(&self, line: &str) -> Option<usize> {
+        match self {
+            Matcher::Fuzzy(fuzzy) => fuzzy.distance(line),
+            Matcher::Word(inner) | Matcher::Line(inner) => inner.distance(line),
+            _ => None,
+        }
+    }
+
+    pub fn is_match(&self, line: &str) -> bool {
+        self.find_at(line, 0).is_some()
+    }
+
+    /// Finds the first occurrence starting at or after byte `start`.
+    pub fn find_at(&self, line: &str, start: usize) -> Option<Range<usize>> {
+        match self {
+            Matcher::Literal(finder) => finder
+                .find_at(line.as_bytes(), start)
+                .map(|i| i..i + finder.needle().len()),
+            Matcher::CaseInsensitive(query) => case::find(line, query, start),
+            Matcher::Regex(regex) => regex.find_at(line, start),
+            Matcher::Multiple(automaton) => automaton.find_at(line, start),
+            Matcher::Fuzzy(fuzzy) => fuzzy.find_at(line, start).map(|(found, _)| found),
+            Matcher::Word(inner) => find_word(inner, line, start),
+            Matcher::Line(inner) => find_line(inner, line, start),
+            Matcher::Expression(expression) => find_expression(expression, line)
+                .into_iter()
+                .find(|found| found.start >= start),
+        }
+    }
+
+    /// Byte ranges of every non-overlapping occurrence in `line`.
+    pub fn find_all(&self, line: &str) -> Vec<Range<usize>> {
+        if let Matcher::Expression(expression) = self {
+            return find_expression(expression, line);
+        }
+
+        let mut ranges = Vec::new();
+        let mut start = 0;
+
+        while start <= line.len() {
+            let found = match self.find_at(line, start) {
+                Some(found) => found,
+                None => break,
+            };
+            start = if found.is_empty() {
+                found.end + line[found.end..].chars().next().map_or(1, char::len_utf8)
+            } else {
+                found.end
+            };
+            ranges.push(found);
+        }
+
+        ranges
+    }
+}
+
+// With `--smart-case`, matching is case sensitive only when some pattern
+// has an uppercase letter.
+fn case_sensitive<I, S>(config: &Config, patterns: I) -> bool
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    if config.smart_case {
+        patterns
+            .into_iter()
+            .any(|pattern| case::has_uppercase(pattern.as_ref(), config.regex))
+    } else {
+        config.case_sensitive
+    }
+}
+
+// What a regex has to match up against, from `-w` and `-x`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Bounds {
+    None,
+    Word,
+    Line,
+}
+
+impl Bounds {
+    fn of(config: &Config) -> Bounds {
+        if config.line_regexp {
+            Bounds::Line
+        } else if config.word_regexp {
+            Bounds::Word
+        } else {
+            Bounds::None
+        }
+    }
+}
+
+// With `bounds`, the pattern is anchored at both ends so a shorter
+// leftmost alternative cannot hide one that reaches the bound, as `a`
+// would in `a|ab`. Errors still point into the pattern as it was written.
+fn compile(
+    pattern: &str,
+    case_sensitive: bool,
+    bounds: Bounds,
+    path: Option<&Path>,
+) -> Result<Regex, MinigrepError> {
+    let build = |pattern: &str| {
+        if case_sensitive {
+            Regex::new(pattern)
+        } else {
+            Regex::new_case_insensitive(pattern)
+        }
+    };
+    let error = |source| MinigrepError::Pattern {
+        path: path.map(Path::to_path_buf),
+        pattern: pattern.to_string(),
+        source,
+    };
+
+    let regex = build(pattern).map_err(error)?;
+    match bounds {
+        Bounds::None => Ok(regex),
+        Bounds::Word => {
+            build(&format!(r"\b{{start-half}}(?:{})\b{{end-half}}", pattern)).map_err(error)
+        }
+        Bounds::Line => build(&format!("^(?:{})$", pattern)).map_err(error),
+    }
+}
+
+// Skips occurrences with a word character right before or after them.
+// Where the longest of several literals is not a whole word, a shorter one
+// starting at the same place may still be.
+fn find_word(inner: &Matcher, line: &str, mut start: usize) -> Option<Range<usize>> {
+    let is_word = |found: &Range<usize>| {
+        let before = line[..found.start].chars().next_back();
+        let after = line[found.end..].chars().next();
+        !before.is_some_and(regex::is_word_char) && !after.is_some_and(regex::is_word_char)
+    };
+
+    while start <= line.len() {
+        let found = inner.find_at(line, start)?;
+        if is_word(&found) {
+            return Some(found);
+        }
+        if let Matcher::Multiple(automaton) = inner {
+            let shorter = automaton
+                .matches_at(line, found.start)
+                .into_iter()
+                .find(is_word);
+            if shorter.is_some() {
+                return shorter;
+            }
+        }
+        start = found.start + line[found.start..].chars().next().map_or(1, char::len_utf8);
+    }
+    None
+}
+
+// Skips occurrences that do not start at the start of a line and end at
+// its end. Only text searched with `--multiline` holds more than one line.
+fn find_line(inner: &Matcher, text: &str, mut start: usize) -> Option<Range<usize>> {
+    while start <= text.len() {
+        let found = inner.find_at(text, start)?;
+        let rest = &text[found.end..];
+        let at_start = found.start == 0 || text[..found.start].ends_with('\n');
+        let at_end = rest.is_empty() || rest.starts_with('\n') || rest.starts_with("\r\n");
+        if at_start && at_end {
+            return Some(found);
+        }
+        start = found.start + text[found.start..].chars().next().map_or(1, char::len_utf8);
+    }
+    None
+}
+
+// When the expression holds for `line`, finds the occurrences of the terms
+// it has outside any NOT. A line selected by NOT alone has none of those,
+// so it gets an empty one to show it was selected.
+fn find_expression(expression: &Expr<Matcher>, line: &str) -> Vec<Range<usize>> {
+    if !expression.eval(&|matcher: &Matcher| matcher.is_match(line)) {
+        return Vec::new();
+    }
+
+    let mut found: Vec<Range<usize>> = expression
+        .positive_terms()
+        .into_iter()
+        .flat_map(|matcher| matcher.find_all(line))
+        .collect();
+    found.sort_by_key(|found| (found.start, Reverse(found.end)));
+
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    for found in found {
+        if ranges.last().is_none_or(|last| found.start >= last.end) {
+            ranges.push(found);
+        }
+    }
+    if ranges.is_empty() {
+        ranges.push(0..0);
+    }
+    ranges
+}
+
+/// A matching line along with where it was found.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Match<'a> {
+    /// One-based line number.
+    pub line_number: usize,
+    /// Byte offset of the start of the line within the searched text.
+    pub byte_offset: usize,
+    pub line: &'a str,
+    /// Byte ranges of each occurrence, relative to the start of the line.
+    pub ranges: Vec<Range<usize>>,
+}
+
+/// Splits text into lines like `str::lines`, keeping the byte offset where
+/// each line starts.
+pub struct Lines<'a> {
+    contents: &'a str,
+    offset: usize,
+}
+
+impl<'a> Lines<'a> {
+    pub fn new(contents: &'a str) -> Lines<'a> {
+        Lines {
+            contents,
+            offset: 0,
+        }
+    }
+}
+
+impl<'a> Iterator for Lines<'a> {
+    type Item = (usize, &'a str);
+
+    fn next(&mut self) -> Option<(usize, &'a str)> {
+        if self.offset >= self.contents.len() {
+            return None;
+        }
+
+        let start = self.offset;
+        let rest = &self.contents[start..];
+        let line = match rest.find('\n') {
+            Some(end) => {
+                self.offset += end + 1;
+                let line = &rest[..end];
+                line.strip_suffix('\r').unwrap_or(line)
+            }
+            None => {
+                self.offset = self.contents.len();
+                rest
+            }
+        };
+
+        Some((start, line))
+    }
+}
+
+/// Lazily yields a `Match` for every line of `contents` the matcher finds.
+pub struct Searcher<'m, 'a> {
+    matcher: &'m Matcher,
+    lines: Lines<'a>,
+    line_number: usize,
+}
+
+impl<'m, 'a> Searcher<'m, 'a> {
+    pub fn new(matcher: &'m Matcher, contents: &'a str) -> Searcher<'m, 'a> {
+        Searcher {
+            matcher,
+            lines: Lines::new(contents),
+            line_number: 0,
+        }
+    }
+}
+
+impl<'m, 'a> Iterator for Searcher<'m, 'a> {
+    type Item = Match<'a>;
+
+    fn next(&mut self) -> Option<Match<'a>> {
+        for (byte_offset, line) in &mut self.lines {
+            self.line_number += 1;
+
+            let ranges = self.matcher.find_all(line);
+            if !ranges.is_empty() {
+                return Some(Match {
+                    line_number: self.line_number,
+                    byte_offset,
+                    line,
+                    ranges,
+                });
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_every_occurrence() {
+        let matcher = Matcher::literal("ab");
+        assert_eq!(vec![0..2, 3..5], matcher.find_all("ab ab a"));
+    }
+
+    #[test]
+    fn case_insensitive_ranges_point_into_original_line() {
+        let matcher = Matcher::case_insensitive("rUsT");
+        assert_eq!(vec![0..4, 7..11], matcher.find_all("Rust: trust"));
+
+        let matcher = Matcher::case_insensitive("é");
+        assert_eq!(vec![2..4], matcher.find_all("CAÉ"));
+
+        let matcher = Matcher::case_insensitive("STRASSE");
+        assert_eq!(vec![0..7, 9..16], matcher.find_all("straße, Strasse"));
+    }
+
+    #[test]
+    fn whole_words_and_lines() {
+        let config = Config {
+            query: String::from("id"),
+            word_regexp: true,
+            ..Config::default()
+        };
+        let matcher = Matcher::new(&config).unwrap();
+        assert_eq!(vec![17..19, 26..28], matcher.find_all("width valid ids (id) id_x id"));
+        assert!(!matcher.is_match("idée"));
+
+        let config = Config {
+            query: String::from("a|ab"),
+            regex: true,
+            line_regexp: true,
+            ..Config::default()
+        };
+        let matcher = Matcher::new(&config).unwrap();
+        assert_eq!(vec![0..2], matcher.find_all("ab"));
+        assert!(!matcher.is_match("abc"));
+
+        let config = Config {
+            query: String::from("ab|abc"),
+            regex: true,
+            word_regexp: true,
+            ..Config::default()
+        };
+        let matcher = Matcher::new(&config).unwrap();
+        assert_eq!(vec![0..3, 4..6], matcher.find_all("abc ab abcd"));
+
+        let config = Config {
+            patterns: vec![String::from("a"), String::from("a-b")],
+            word_regexp: true,
+            ..Config::default()
+        };
+        let matcher = Matcher::new(&config).unwrap();
+        assert_eq!(vec![0..1], matcher.find_all("a-bc"));
+        assert_eq!(vec![0..3], matcher.find_all("a-b c"));
+    }
+
+    #[test]
+    fn smart_case_depends_on_the_query() {
+        let mut config = Config {
+            smart_case: true,
+            query: String::from("rust"),
+            ..Config::default()
+        };
+        assert!(Matcher::new(&config).unwrap().is_match("RUST"));
+
+        config.query = String::from("Rust");
+        assert!(!Matcher::new(&config).unwrap().is_match("RUST"));
+
+        config.regex = true;
+        config.query = String::from(r"r\Sst");
+        assert!(Matcher::new(&config).unwrap().is_match("RUST"));
+    }
+
+    #[test]
+    fn expressions_select_lines() {
+        let config = Config {
+            expression: Some(Expr::parse("timeout AND (db OR cache) AND NOT retry").unwrap()),
+            case_sensitive: false,
+            ..Config::default()
+        };
+        let matcher = Matcher::new(&config).unwrap();
+        assert_eq!(vec![0..7, 19..21], matcher.find_all("Timeout talking to DB"));
+        assert!(matcher.is_match("cache timeout"));
+        assert!(!matcher.is_match("db timeout, retry 2"));
+        assert!(!matcher.is_match("db error"));
+
+        let config = Config {
+            expression: Some(Expr::parse("NOT id OR valid").unwrap()),
+            word_regexp: true,
+            ..Config::default()
+        };
+        let matcher = Matcher::new(&config).unwrap();
+        assert_eq!(vec![0..0], matcher.find_all("width"));
+        assert_eq!(vec![6..11], matcher.find_all("is id valid"));
+        assert!(!matcher.is_match("an id"));
+    }
+
+    #[test]
+    fn searcher_reports_positions() {
+        let matcher = Matcher::Regex(Regex::new("o").unwrap());
+        let contents = "foo\r\nbar\nboo boo\n";
+        let matches: Vec<Match> = Searcher::new(&matcher, contents).collect();
+
+        assert_eq!(
+            vec![
+                Match {
+                    line_number: 1,
+                    byte_offset: 0,
+                    line: "foo",
+                    ranges: vec![1..2, 2..3],
+                },
+                Match {
+                    line_number: 3,
+                    byte_offset: 9,
+                    line: "boo boo",
+                    ranges: vec![1..2, 2..3, 5..6, 6..7],
+                },
+            ],
+            matches
+        );
+    }
+}
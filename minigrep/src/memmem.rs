@@ -0,0 +1,284 @@
+// Substring search for plain literal queries. Most of the time is spent
+// looking for the needle's rarest byte, eight bytes at a time, and the
+// whole needle is only compared where it turns up along with the second
+// rarest. When the rarest byte turns out to be common in the haystack,
+// the search moves on to looking for both rare bytes at once, eight
+// windows at a time. When even that finds mostly false candidates, it
+// falls back to Boyer-Moore-Horspool, which skips ahead by up to the
+// length of the needle after a mismatch.
+
+// Move on from a prefilter after this many false candidates, if they came
+// on average closer together than `PREFILTER_MIN_SKIP` bytes.
+const PREFILTER_MAX_MISSES: usize = 32;
+const PREFILTER_MIN_SKIP: usize = 16;
+
+// Bytes roughly ordered from most to least common in text and source
+// code. Bytes not listed are taken to be rarer than all of these.
+const COMMON_BYTES: &[u8] = b" etaoinsrhldcumfpgwybvk\nxjqz,.ETAOINSRHLDCUMFPGWYBVKXJQZ\
+                              0123456789-_()=;:\"'/\t{}<>#*[]\r";
+
+const LOW: u64 = u64::from_le_bytes([0x01; 8]);
+const HIGH: u64 = u64::from_le_bytes([0x80; 8]);
+
+#[derive(Debug, Clone)]
+pub struct Finder {
+    needle: Vec<u8>,
+    // The indexes in the needle of its two rarest bytes.
+    rare: (usize, usize),
+    // How far the window may move when its last byte is a given byte.
+    shift: Box<[usize; 256]>,
+}
+
+impl Finder {
+    pub fn new(needle: &[u8]) -> Finder {
+        let mut by_rarity: Vec<usize> = (0..needle.len()).collect();
+        by_rarity.sort_by_key(|&i| std::cmp::Reverse((rarity(needle[i]), i)));
+        let first = by_rarity.first().cloned().unwrap_or(0);
+        // A second byte equal to the first tells little more.
+        let second = by_rarity
+            .iter()
+            .cloned()
+            .find(|&i| needle[i] != needle[first])
+            .or_else(|| by_rarity.get(1).cloned())
+            .unwrap_or(first);
+
+        let mut shift = Box::new([needle.len().max(1); 256]);
+        for (i, &b) in needle
+            .iter()
+            .enumerate()
+            .take(needle.len().saturating_sub(1))
+        {
+            shift[b as usize] = needle.len() - 1 - i;
+        }
+
+        Finder {
+            needle: needle.to_vec(),
+            rare: (first, second),
+            shift,
+        }
+    }
+
+    pub fn needle(&self) -> &[u8] {
+        &self.needle
+    }
+
+    /// The position of the first occurrence of the needle that starts at or
+    /// after `start`.
+    pub fn find_at(&self, haystack: &[u8], start: usize) -> Option<usize> {
+        let n = self.needle.len();
+        if start > haystack.len() {
+            return None;
+        }
+        if n == 0 {
+            return Some(start);
+        }
+        if n == 1 {
+            return memchr(self.needle[0], &haystack[start..]).map(|i| start + i);
+        }
+
+        // Windows starting past here cannot hold the needle.
+        let last_start = haystack.len().checked_sub(n)?;
+        let (first, second) = self.rare;
+        let mut i = start;
+        let mut misses = 0;
+
+        while i <= last_start {
+            let from = i + first;
+            let candidate =
+                memchr(self.needle[first], &haystack[from..last_start + first + 1])? + from - first;
+            if haystack[candidate + second] == self.needle[second]
+                && self.is_at(haystack, candidate)
+            {
+                return Some(candidate);
+            }
+
+            i = candidate + 1;
+            misses += 1;
+            if is_dense(misses, i - start) {
+                return self.pairs(haystack, i, last_start);
+            }
+        }
+
+        None
+    }
+
+    // Looks at eight windows at a time, comparing the bytes where both
+    // rare bytes would be in each.
+    fn pairs(&self, haystack: &[u8], start: usize, last_start: usize) -> Option<usize> {
+        let (first, second) = self.rare;
+        let first_bytes = u64::from_le_bytes([self.needle[first]; 8]);
+        let second_bytes = u64::from_le_bytes([self.needle[second]; 8]);
+        let mut i = start;
+        let mut misses = 0;
+
+        let firsts = haystack[start + first..].chunks_exact(8);
+        let seconds = haystack[start + second..].chunks_exact(8);
+        for (a, b) in firsts.zip(seconds) {
+            let x = (word(a) ^ first_bytes) | (word(b) ^ second_bytes);
+            let mut candidates = x.wrapping_sub(LOW) & !x & HIGH;
+            while candidates != 0 {
+                let candidate = i + candidates.trailing_zeros() as usize / 8;
+                if candidate > last_start {
+                    return None;
+                }
+                if self.is_at(haystack, candidate) {
+                    return Some(candidate);
+                }
+                misses += 1;
+                candidates &= candidates - 1;
+            }
+            i += 8;
+
+            if is_dense(misses, i - start) {
+                return self.horspool(haystack, i);
+            }
+        }
+
+        (i..=last_start).find(|&i| self.is_at(haystack, i))
+    }
+
+    fn is_at(&self, haystack: &[u8], i: usize) -> bool {
+        haystack.get(i..i + self.needle.len()) == Some(&self.needle[..])
+    }
+
+    fn horspool(&self, haystack: &[u8], mut i: usize) -> Option<usize> {
+        let n = self.needle.len();
+        let last = self.needle[n - 1];
+
+        while i + n <= haystack.len() {
+            let end = haystack[i + n - 1];
+            if end == last && haystack[i..i + n - 1] == self.needle[..n - 1] {
+                return Some(i);
+            }
+            i += self.shift[end as usize];
+        }
+
+        None
+    }
+}
+
+// Whether false candidates come too often for a prefilter to pay off.
+fn is_dense(misses: usize, searched: usize) -> bool {
+    misses > PREFILTER_MAX_MISSES && searched < misses * PREFILTER_MIN_SKIP
+}
+
+fn rarity(b: u8) -> usize {
+    COMMON_BYTES
+        .iter()
+        .position(|&common| common == b)
+        .unwrap_or(COMMON_BYTES.len())
+}
+
+// Eight bytes as a word, the first in the lowest bits.
+fn word(bytes: &[u8]) -> u64 {
+    let mut word = [0; 8];
+    word.copy_from_slice(bytes);
+    u64::from_le_bytes(word)
+}
+
+/// The position of the first `byte` in `haystack`. Compares a word at a
+/// time, using the usual trick for spotting a zero byte in a word: it
+/// finds every zero byte, and may flag a byte right after one.
+pub fn memchr(byte: u8, haystack: &[u8]) -> Option<usize> {
+    let repeated = u64::from_le_bytes([byte; 8]);
+    let mut chunks = haystack.chunks_exact(8);
+
+    for (n, chunk) in chunks.by_ref().enumerate() {
+        let x = word(chunk) ^ repeated;
+        let found = x.wrapping_sub(LOW) & !x & HIGH;
+        if found != 0 {
+            // The lowest flagged byte is always a real one.
+            return Some(n * 8 + found.trailing_zeros() as usize / 8);
+        }
+    }
+
+    let rest = chunks.remainder();
+    rest.iter()
+        .position(|&b| b == byte)
+        .map(|i| haystack.len() - rest.len() + i)
+}
+
+/// The number of times `byte` appears in `haystack`, counted a word at a
+/// time. Unlike the trick in `memchr`, clearing the high bit first keeps
+/// the addition from carrying into the next byte, so every zero byte and
+/// nothing else is flagged.
+pub fn count(byte: u8, haystack: &[u8]) -> usize {
+    let repeated = u64::from_le_bytes([byte; 8]);
+    let low_bits = !HIGH;
+    let mut chunks = haystack.chunks_exact(8);
+    let mut count = 0;
+
+    for chunk in chunks.by_ref() {
+        let x = word(chunk) ^ repeated;
+        let nonzero = ((x & low_bits).wrapping_add(low_bits) | x) & HIGH;
+        count += 8 - nonzero.count_ones() as usize;
+    }
+
+    count + chunks.remainder().iter().filter(|&&b| b == byte).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find_all(needle: &str, haystack: &str) -> Vec<usize> {
+        let finder = Finder::new(needle.as_bytes());
+        let mut found = Vec::new();
+        let mut start = 0;
+        while let Some(i) = finder.find_at(haystack.as_bytes(), start) {
+            found.push(i);
+            start = i + 1;
+        }
+        found
+    }
+
+    #[test]
+    fn finds_bytes_a_word_at_a_time() {
+        let haystack = b"0123456789abcdefghij";
+        for (i, &b) in haystack.iter().enumerate() {
+            assert_eq!(Some(i), memchr(b, haystack));
+        }
+        assert_eq!(None, memchr(b'z', haystack));
+        assert_eq!(Some(1), memchr(0x80, &[0x7f, 0x80]));
+    }
+
+    #[test]
+    fn counts_bytes_a_word_at_a_time() {
+        let haystack = b"a\nb\n\n\x8a\x0b\n\xff\n0123456789\n\n";
+        for len in 0..=haystack.len() {
+            let expected = haystack[..len].iter().filter(|&&b| b == b'\n').count();
+            assert_eq!(expected, count(b'\n', &haystack[..len]), "{}", len);
+        }
+        assert_eq!(3, count(0x80, &[0x80, 0x00, 0x80, 0x7f, 0x80, 0x01, 0, 0]));
+    }
+
+    #[test]
+    fn finds_every_occurrence() {
+        let haystack = "the theme of these three threads: the end, then the rest. ".repeat(20);
+        for needle in ["the", "th", "t", "three", "rest. the", "zzz", "ee"] {
+            let expected: Vec<usize> = (0..=haystack.len() - needle.len())
+                .filter(|&i| haystack[i..].starts_with(needle))
+                .collect();
+            assert_eq!(expected, find_all(needle, &haystack), "{:?}", needle);
+        }
+        assert_eq!(Some(3), Finder::new(b"").find_at(b"abc", 3));
+        assert_eq!(None, Finder::new(b"").find_at(b"abc", 4));
+    }
+
+    #[test]
+    fn falls_back_when_rare_bytes_are_common() {
+        // Every `q` is a false candidate until the end.
+        let haystack = format!("{}quux", "aq".repeat(1000));
+        assert_eq!(
+            Some(2000),
+            Finder::new(b"quux").find_at(haystack.as_bytes(), 0)
+        );
+        // And so is every `q` with an `x` three bytes on.
+        let haystack = format!("{}quux", "qaax".repeat(1000));
+        assert_eq!(
+            Some(4000),
+            Finder::new(b"quux").find_at(haystack.as_bytes(), 0)
+        );
+        assert_eq!(vec![1, 5], find_all("aab", "aaabaaab"));
+    }
+}
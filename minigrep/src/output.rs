@@ -0,0 +1,194 @@
+use std::io::{self, Write};
+use std::sync::{Condvar, Mutex};
+
+// Standard output shared by the files searched in parallel. Each file's
+// output is gathered in a small buffer and written out a chunk at a time,
+// so memory does not grow with the amount of output. To keep a file's
+// output in one piece, the file holds standard output from its first chunk
+// until it is done, and other files wait for their turn before writing.
+// With `ordered`, turns go in the order of the files' indexes.
+
+/// How much of a file's output to gather before writing it out.
+pub const CHUNK_SIZE: usize = 64 * 1024;
+
+pub struct Output {
+    ordered: bool,
+    state: Mutex<Turn>,
+    changed: Condvar,
+}
+
+struct Turn {
+    // The index of the next file to write, when ordered.
+    next: usize,
+    // Whether some file is writing, when not.
+    busy: bool,
+}
+
+impl Output {
+    pub fn new(ordered: bool) -> Output {
+        Output {
+            ordered,
+            state: Mutex::new(Turn {
+                next: 0,
+                busy: false,
+            }),
+            changed: Condvar::new(),
+        }
+    }
+
+    /// The output of the file at `index`, written out whenever at least
+    /// `chunk_size` bytes of whole lines have gathered. With `take_turns`
+    /// unset, lines are written as soon as they are complete, and may come
+    /// between the lines of other files.
+    pub fn file(&self, index: usize, chunk_size: usize, take_turns: bool) -> FileOutput<'_> {
+        FileOutput {
+            output: self,
+            index,
+            buffer: Vec::new(),
+            chunk_size,
+            take_turns,
+            has_turn: false,
+        }
+    }
+
+    fn wait_for_turn(&self, index: usize) {
+        let mut turn = self.state.lock().unwrap();
+        if self.ordered {
+            while turn.next != index {
+                turn = self.changed.wait(turn).unwrap();
+            }
+        } else {
+            while turn.busy {
+                turn = self.changed.wait(turn).unwrap();
+            }
+            turn.busy = true;
+        }
+    }
+
+    fn end_turn(&self) {
+        let mut turn = self.state.lock().unwrap();
+        if self.ordered {
+            turn.next += 1;
+        } else {
+            turn.busy = false;
+        }
+        self.changed.notify_all();
+    }
+}
+
+pub struct FileOutput<'o> {
+    output: &'o Output,
+    index: usize,
+    buffer: Vec<u8>,
+    chunk_size: usize,
+    take_turns: bool,
+    has_turn: bool,
+}
+
+impl FileOutput<'_> {
+    /// Called after each whole line, or whatever else should not be split
+    /// from what comes after it.
+    pub fn end_line(&mut self) -> io::Result<()> {
+        if self.buffer.len() >= self.chunk_size {
+            self.write_out()?;
+        }
+        Ok(())
+    }
+
+    /// Writes out whatever is left. Dropping the output does the same but
+    /// ignores errors.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.write_out()
+    }
+
+    fn write_out(&mut self) -> io::Result<()> {
+        if self.take_turns && !self.has_turn {
+            self.output.wait_for_turn(self.index);
+            self.has_turn = true;
+        }
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let result = io::stdout().lock().write_all(&self.buffer);
+        self.buffer.clear();
+        result
+    }
+}
+
+impl Write for FileOutput<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// Even a file that failed, or printed nothing, has to take its turn so
+// that the files after it get theirs.
+impl Drop for FileOutput<'_> {
+    fn drop(&mut self) {
+        let _ = self.write_out();
+        if self.has_turn {
+            self.output.end_turn();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parallel;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn turns_follow_file_order() {
+        let output = Output::new(true);
+        let order = Mutex::new(Vec::new());
+
+        parallel::for_each(
+            &[30, 0, 10, 0],
+            4,
+            false,
+            |index, &delay| {
+                thread::sleep(Duration::from_millis(delay));
+                let mut file = output.file(index, 0, true);
+                file.write_out().unwrap();
+                order.lock().unwrap().push(index);
+            },
+            |_| {},
+        );
+
+        assert_eq!(vec![0, 1, 2, 3], order.into_inner().unwrap());
+    }
+
+    #[test]
+    fn one_file_writes_at_a_time() {
+        let output = Output::new(false);
+        let writing = Mutex::new(0);
+        let most = Mutex::new(0);
+
+        parallel::for_each(
+            &[0; 8],
+            4,
+            false,
+            |index, _| {
+                let mut file = output.file(index, 0, true);
+                file.write_out().unwrap();
+                *writing.lock().unwrap() += 1;
+                let now = *writing.lock().unwrap();
+                let mut most = most.lock().unwrap();
+                *most = (*most).max(now);
+                drop(most);
+                thread::sleep(Duration::from_millis(5));
+                *writing.lock().unwrap() -= 1;
+            },
+            |_| {},
+        );
+
+        assert_eq!(1, most.into_inner().unwrap());
+    }
+}
@@ -0,0 +1,95 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+/// Calls `work` on every item, along with its index, using up to `threads`
+/// worker threads, and hands each result to `done` on the calling thread.
+/// Items are started in order. When `ordered` is
+/// set, results are delivered in the order of `items`; otherwise as soon
+/// as they are ready.
+pub fn for_each<'a, T, R, W, D>(items: &'a [T], threads: usize, ordered: bool, work: W, mut done: D)
+where
+    T: Sync,
+    R: Send,
+    W: Fn(usize, &'a T) -> R + Sync,
+    D: FnMut(R),
+{
+    let next_item = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+
+    thread::scope(|scope| {
+        for _ in 0..threads.clamp(1, items.len().max(1)) {
+            let sender = sender.clone();
+            let next_item = &next_item;
+            let work = &work;
+
+            scope.spawn(move || loop {
+                let index = next_item.fetch_add(1, Ordering::SeqCst);
+                if index >= items.len() {
+                    break;
+                }
+                if sender.send((index, work(index, &items[index]))).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        let mut pending = BTreeMap::new();
+        let mut next_result = 0;
+
+        for (index, result) in receiver {
+            if !ordered {
+                done(result);
+                continue;
+            }
+
+            pending.insert(index, result);
+            while let Some(result) = pending.remove(&next_result) {
+                done(result);
+                next_result += 1;
+            }
+        }
+    });
+}
+
+/// The number of worker threads to use when none was asked for.
+pub fn default_threads() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ordered_results_follow_input_order() {
+        let items: Vec<u64> = (0..50).collect();
+        let mut results = Vec::new();
+
+        for_each(
+            &items,
+            4,
+            true,
+            |_, &n| {
+                thread::sleep(std::time::Duration::from_millis(50 - n));
+                n * 2
+            },
+            |n| results.push(n),
+        );
+
+        assert_eq!(items.iter().map(|n| n * 2).collect::<Vec<_>>(), results);
+    }
+
+    #[test]
+    fn unordered_results_are_all_delivered() {
+        let items: Vec<u64> = (0..20).collect();
+        let mut results = Vec::new();
+
+        for_each(&items, 3, false, |_, &n| n + 1, |n| results.push(n));
+        results.sort();
+
+        assert_eq!((1..21).collect::<Vec<_>>(), results);
+    }
+}
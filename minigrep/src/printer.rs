@@ -0,0 +1,204 @@
+use std::io::{self, Write};
+use std::ops::Range;
+use std::path::Path;
+
+use crate::color::Colors;
+use crate::Config;
+
+// Formats search results as text, grep style. Matching lines use `:` to
+// separate their prefix fields and context lines use `-`.
+
+pub struct Printer {
+    pub line_number: bool,
+    pub byte_offset: bool,
+    /// Colors to use, or None for plain output.
+    pub colors: Option<Colors>,
+}
+
+impl Printer {
+    pub fn new(config: &Config) -> Printer {
+        Printer {
+            line_number: config.line_number,
+            byte_offset: config.byte_offset,
+            colors: if config.color.enabled() {
+                Some(config.colors)
+            } else {
+                None
+            },
+        }
+    }
+
+    /// Writes a line prefixed by whichever of its path, line number and
+    /// byte offset were asked for, and by its edit `distance` from the query
+    /// with `--fuzzy`, highlighting the byte `ranges` in it.
+    #[allow(clippy::too_many_arguments)]
+    pub fn write_line<W: Write>(
+        &self,
+        out: &mut W,
+        path: Option<&Path>,
+        line_number: usize,
+        byte_offset: usize,
+        line: &str,
+        ranges: &[Range<usize>],
+        distance: Option<usize>,
+        separator: char,
+    ) -> io::Result<()> {
+        if let Some(path) = path {
+            self.write_path(out, path)?;
+            self.write_field_separator(out, separator)?;
+        }
+        if self.line_number {
+            self.write_number(out, line_number)?;
+            self.write_field_separator(out, separator)?;
+        }
+        if self.byte_offset {
+            self.write_number(out, byte_offset)?;
+            self.write_field_separator(out, separator)?;
+        }
+        if let Some(distance) = distance {
+            write!(out, "~")?;
+            self.write_number(out, distance)?;
+            self.write_field_separator(out, separator)?;
+        }
+
+        match &self.colors {
+            Some(colors) if !ranges.is_empty() => {
+                let mut last = 0;
+                // An empty occurrence has nothing to paint.
+                for range in ranges.iter().filter(|range| !range.is_empty()) {
+                    write!(out, "{}", &line[last..range.start])?;
+                    write!(out, "{}", colors.matched.paint(&line[range.clone()]))?;
+                    last = range.end;
+                }
+                writeln!(out, "{}", &line[last..])
+            }
+            _ => writeln!(out, "{}", line),
+        }
+    }
+
+    /// Writes the `--` placed between groups of context lines.
+    pub fn write_context_break<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        match &self.colors {
+            Some(colors) => writeln!(out, "{}", colors.separator.paint("--")),
+            None => writeln!(out, "--"),
+        }
+    }
+
+    /// Writes the number of selected lines in a file.
+    pub fn write_count<W: Write>(
+        &self,
+        out: &mut W,
+        path: Option<&Path>,
+        count: usize,
+    ) -> io::Result<()> {
+        if let Some(path) = path {
+            self.write_path(out, path)?;
+            self.write_field_separator(out, ':')?;
+        }
+        writeln!(out, "{}", count)
+    }
+
+    /// Writes the notice that stands in for the lines selected from a
+    /// binary file.
+    pub fn write_binary_match<W: Write>(&self, out: &mut W, path: &Path) -> io::Result<()> {
+        write!(out, "Binary file ")?;
+        self.write_path(out, path)?;
+        writeln!(out, " matches")
+    }
+
+    /// Writes a path on a line of its own.
+    pub fn write_path_line<W: Write>(&self, out: &mut W, path: &Path) -> io::Result<()> {
+        self.write_path(out, path)?;
+        writeln!(out)
+    }
+
+    fn write_path<W: Write>(&self, out: &mut W, path: &Path) -> io::Result<()> {
+        let path = path.display().to_string();
+        match &self.colors {
+            Some(colors) => write!(out, "{}", colors.path.paint(&path)),
+            None => write!(out, "{}", path),
+        }
+    }
+
+    fn write_number<W: Write>(&self, out: &mut W, number: usize) -> io::Result<()> {
+        match &self.colors {
+            Some(colors) => write!(out, "{}", colors.line_number.paint(&number.to_string())),
+            None => write!(out, "{}", number),
+        }
+    }
+
+    fn write_field_separator<W: Write>(&self, out: &mut W, separator: char) -> io::Result<()> {
+        match &self.colors {
+            Some(colors) => write!(out, "{}", colors.separator.paint(&separator.to_string())),
+            None => write!(out, "{}", separator),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn printer(colors: Option<Colors>) -> Printer {
+        Printer {
+            line_number: true,
+            byte_offset: false,
+            colors,
+        }
+    }
+
+    #[test]
+    fn plain_output() {
+        let mut out = Vec::new();
+        printer(None)
+            .write_line(
+                &mut out,
+                Some(Path::new("a.txt")),
+                3,
+                0,
+                "a foo b",
+                &[2..5, 6..7],
+                None,
+                ':',
+            )
+            .unwrap();
+
+        assert_eq!("a.txt:3:a foo b\n", String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn shows_fuzzy_distance() {
+        let mut out = Vec::new();
+        printer(None)
+            .write_line(&mut out, None, 3, 0, "a fob", &[], Some(1), ':')
+            .unwrap();
+
+        assert_eq!("3:~1:a fob\n", String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn highlights_matches() {
+        let mut out = Vec::new();
+        printer(Some(Colors::default()))
+            .write_line(&mut out, None, 3, 0, "a foo b foo", &[2..5, 8..11], None, ':')
+            .unwrap();
+
+        assert_eq!(
+            "\x1b[32m3\x1b[0m\x1b[36m:\x1b[0ma \x1b[1;31mfoo\x1b[0m b \x1b[1;31mfoo\x1b[0m\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn leaves_empty_matches_unpainted() {
+        let mut out = Vec::new();
+        printer(Some(Colors::default()))
+            .write_line(&mut out, None, 3, 0, "a foo", &[0..0, 2..5, 5..5], None, ':')
+            .unwrap();
+
+        assert_eq!(
+            "\x1b[32m3\x1b[0m\x1b[36m:\x1b[0ma \x1b[1;31mfoo\x1b[0m\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+}
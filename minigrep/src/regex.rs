@@ -0,0 +1,995 @@
+use std::error::Error;
+use std::fmt;
+use std::ops::Range;
+
+use crate::case;
+
+// A small regular expression engine. Patterns are parsed into a syntax
+// tree, compiled into a list of instructions and run with a Pike VM, so
+// matching time stays linear in the size of the input.
+//
+// Supported syntax:
+//   literals, `.`, `[a-z]`, `[^0-9]`, `\d \w \s \D \W \S`, `\b \B`,
+//   `\b{start-half} \b{end-half}` (no word character before or after),
+//   `^ $` (at the start and end of each line), `a|b`, `(group)`, `(?:group)`, `* + ? {n} {n,} {n,m}`,
+//   lazy repetition (`*?`, `+?`, ...) and the inline flag `(?i)`.
+
+const MAX_REPEAT: u32 = 1000;
+
+// Counted repetition is compiled by repeating its body, so nesting it can
+// make a program of any size. Past this many instructions, compiling stops.
+const MAX_PROGRAM: usize = 100_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegexError {
+    pub message: String,
+    pub position: usize,
+}
+
+impl fmt::Display for RegexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "regex parse error at position {}: {}",
+            self.position, self.message
+        )
+    }
+}
+
+impl Error for RegexError {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Perl {
+    Digit,
+    Word,
+    Space,
+}
+
+impl Perl {
+    fn matches(self, c: char) -> bool {
+        match self {
+            Perl::Digit => c.is_ascii_digit(),
+            Perl::Word => is_word_char(c),
+            Perl::Space => c.is_whitespace(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum ClassItem {
+    Range(char, char),
+    Perl(Perl, bool),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Class {
+    items: Vec<ClassItem>,
+    negated: bool,
+    case_insensitive: bool,
+}
+
+impl Class {
+    fn matches(&self, c: char) -> bool {
+        let found = if self.case_insensitive {
+            self.contains(c)
+                || self.contains(case::fold_simple(c))
+                || c.to_lowercase().any(|l| self.contains(l))
+                || c.to_uppercase().any(|u| self.contains(u))
+        } else {
+            self.contains(c)
+        };
+
+        found != self.negated
+    }
+
+    fn contains(&self, c: char) -> bool {
+        self.items.iter().any(|item| match *item {
+            ClassItem::Range(start, end) => start <= c && c <= end,
+            ClassItem::Perl(perl, negated) => perl.matches(c) != negated,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Assertion {
+    Start,
+    End,
+    WordBoundary,
+    NotWordBoundary,
+    NoWordBefore,
+    NoWordAfter,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Flags {
+    case_insensitive: bool,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Empty,
+    Literal(char, bool),
+    Any,
+    Class(Class),
+    Assert(Assertion),
+    Group(Box<Node>, Option<usize>),
+    Concat(Vec<Node>),
+    Alternate(Vec<Node>),
+    Repeat {
+        node: Box<Node>,
+        min: u32,
+        max: Option<u32>,
+        greedy: bool,
+    },
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    groups: usize,
+}
+
+impl Parser {
+    fn new(pattern: &str) -> Parser {
+        Parser {
+            chars: pattern.chars().collect(),
+            pos: 0,
+            groups: 0,
+        }
+    }
+
+    fn error<T>(&self, message: &str) -> Result<T, RegexError> {
+        Err(RegexError {
+            message: message.to_string(),
+            position: self.pos,
+        })
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).cloned()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).cloned()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        if c.is_some() {
+            self.pos += 1;
+        }
+        c
+    }
+
+    fn parse(&mut self, flags: Flags) -> Result<Node, RegexError> {
+        let node = self.parse_alternation(flags)?;
+        if self.peek().is_some() {
+            return self.error("unmatched closing parenthesis");
+        }
+        Ok(node)
+    }
+
+    fn parse_alternation(&mut self, mut flags: Flags) -> Result<Node, RegexError> {
+        let mut branches = vec![self.parse_concat(&mut flags)?];
+
+        while self.peek() == Some('|') {
+            self.next();
+            branches.push(self.parse_concat(&mut flags)?);
+        }
+
+        if branches.len() == 1 {
+            Ok(branches.pop().unwrap())
+        } else {
+            Ok(Node::Alternate(branches))
+        }
+    }
+
+    fn parse_concat(&mut self, flags: &mut Flags) -> Result<Node, RegexError> {
+        let mut nodes = Vec::new();
+
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            if c == '(' && self.peek_at(1) == Some('?') && self.parse_inline_flags(flags)? {
+                continue;
+            }
+            let atom = self.parse_atom(*flags)?;
+            nodes.push(self.parse_repeat(atom)?);
+        }
+
+        match nodes.len() {
+            0 => Ok(Node::Empty),
+            1 => Ok(nodes.pop().unwrap()),
+            _ => Ok(Node::Concat(nodes)),
+        }
+    }
+
+    // Consumes `(?i)` style groups, which change the flags for the rest of
+    // the enclosing group. Returns false for anything else, like `(?:`.
+    fn parse_inline_flags(&mut self, flags: &mut Flags) -> Result<bool, RegexError> {
+        let mut offset = 2;
+        let mut enable = true;
+        let mut updated = *flags;
+
+        loop {
+            match self.peek_at(offset) {
+                Some(')') if offset > 2 => break,
+                Some('-') if enable => enable = false,
+                Some('i') => updated.case_insensitive = enable,
+                _ => return Ok(false),
+            }
+            offset += 1;
+        }
+
+        self.pos += offset + 1;
+        *flags = updated;
+        Ok(true)
+    }
+
+    fn parse_repeat(&mut self, atom: Node) -> Result<Node, RegexError> {
+        let mut node = atom;
+
+        loop {
+            let (min, max) = match self.peek() {
+                Some('*') => {
+                    self.next();
+                    (0, None)
+                }
+                Some('+') => {
+                    self.next();
+                    (1, None)
+                }
+                Some('?') => {
+                    self.next();
+                    (0, Some(1))
+                }
+                Some('{') => match self.parse_counted()? {
+                    Some(bounds) => bounds,
+                    None => return Ok(node),
+                },
+                _ => return Ok(node),
+            };
+
+            if let Node::Assert(_) | Node::Empty = node {
+                return self.error("repetition operator missing expression");
+            }
+
+            let greedy = if self.peek() == Some('?') {
+                self.next();
+                false
+            } else {
+                true
+            };
+
+            node = Node::Repeat {
+                node: Box::new(node),
+                min,
+                max,
+                greedy,
+            };
+        }
+    }
+
+    // Parses `{n}`, `{n,}` or `{n,m}`. A brace that does not start a valid
+    // counted repetition is left alone and treated as a literal.
+    fn parse_counted(&mut self) -> Result<Option<(u32, Option<u32>)>, RegexError> {
+        let start = self.pos;
+        self.next();
+
+        let min = match self.parse_number() {
+            Some(n) => n,
+            None => {
+                self.pos = start;
+                return Ok(None);
+            }
+        };
+
+        let max = if self.peek() == Some(',') {
+            self.next();
+            self.parse_number()
+        } else {
+            Some(min)
+        };
+
+        if self.peek() != Some('}') {
+            self.pos = start;
+            return Ok(None);
+        }
+        self.next();
+
+        if min > MAX_REPEAT || max.is_some_and(|m| m > MAX_REPEAT) {
+            return self.error("repetition count too large");
+        }
+        if max.is_some_and(|m| m < min) {
+            return self.error("invalid repetition range");
+        }
+
+        Ok(Some((min, max)))
+    }
+
+    fn parse_number(&mut self) -> Option<u32> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.next();
+        }
+        if start == self.pos {
+            return None;
+        }
+        self.chars[start..self.pos]
+            .iter()
+            .collect::<String>()
+            .parse()
+            .ok()
+    }
+
+    fn parse_atom(&mut self, flags: Flags) -> Result<Node, RegexError> {
+        match self.next().unwrap() {
+            '(' => self.parse_group(flags),
+            '[' => self.parse_class(flags).map(Node::Class),
+            '.' => Ok(Node::Any),
+            '^' => Ok(Node::Assert(Assertion::Start)),
+            '$' => Ok(Node::Assert(Assertion::End)),
+            '\\' => self.parse_escape(flags),
+            '*' | '+' | '?' => {
+                self.pos -= 1;
+                self.error("repetition operator missing expression")
+            }
+            c => Ok(Node::Literal(
+                fold(c, flags.case_insensitive),
+                flags.case_insensitive,
+            )),
+        }
+    }
+
+    fn parse_group(&mut self, flags: Flags) -> Result<Node, RegexError> {
+        let index = if self.peek() == Some('?') && self.peek_at(1) == Some(':') {
+            self.pos += 2;
+            None
+        } else if self.peek() == Some('?') {
+            return self.error("unsupported group syntax");
+        } else {
+            self.groups += 1;
+            Some(self.groups)
+        };
+
+        let node = self.parse_alternation(flags)?;
+        if self.next() != Some(')') {
+            return self.error("unclosed group");
+        }
+
+        Ok(Node::Group(Box::new(node), index))
+    }
+
+    fn parse_class(&mut self, flags: Flags) -> Result<Class, RegexError> {
+        let mut class = Class {
+            items: Vec::new(),
+            negated: false,
+            case_insensitive: flags.case_insensitive,
+        };
+
+        if self.peek() == Some('^') {
+            self.next();
+            class.negated = true;
+        }
+
+        let mut first = true;
+        loop {
+            let c = match self.next() {
+                Some(c) => c,
+                None => return self.error("unclosed character class"),
+            };
+
+            if c == ']' && !first {
+                break;
+            }
+            first = false;
+
+            let start = if c == '\\' {
+                match self.parse_class_escape()? {
+                    ClassItem::Range(c, _) => c,
+                    perl => {
+                        class.items.push(perl);
+                        continue;
+                    }
+                }
+            } else {
+                c
+            };
+
+            if self.peek() == Some('-') && self.peek_at(1).is_some_and(|c| c != ']') {
+                self.next();
+                let end = match self.next().unwrap() {
+                    '\\' => match self.parse_class_escape()? {
+                        ClassItem::Range(c, _) => c,
+                        _ => return self.error("invalid range end in character class"),
+                    },
+                    c => c,
+                };
+                if end < start {
+                    return self.error("invalid range in character class");
+                }
+                class.items.push(ClassItem::Range(start, end));
+            } else {
+                class.items.push(ClassItem::Range(start, start));
+            }
+        }
+
+        Ok(class)
+    }
+
+    fn parse_class_escape(&mut self) -> Result<ClassItem, RegexError> {
+        match self.parse_escape(Flags {
+            case_insensitive: false,
+        })? {
+            Node::Literal(c, _) => Ok(ClassItem::Range(c, c)),
+            Node::Class(mut class) => Ok(class.items.pop().unwrap()),
+            _ => self.error("invalid escape in character class"),
+        }
+    }
+
+    fn parse_escape(&mut self, flags: Flags) -> Result<Node, RegexError> {
+        let c = match self.next() {
+            Some(c) => c,
+            None => return self.error("trailing backslash"),
+        };
+
+        let perl = |perl, negated| {
+            Node::Class(Class {
+                items: vec![ClassItem::Perl(perl, negated)],
+                negated: false,
+                case_insensitive: false,
+            })
+        };
+        let literal = |c| Node::Literal(fold(c, flags.case_insensitive), flags.case_insensitive);
+
+        match c {
+            'd' => Ok(perl(Perl::Digit, false)),
+            'D' => Ok(perl(Perl::Digit, true)),
+            'w' => Ok(perl(Perl::Word, false)),
+            'W' => Ok(perl(Perl::Word, true)),
+            's' => Ok(perl(Perl::Space, false)),
+            'S' => Ok(perl(Perl::Space, true)),
+            'b' => {
+                for (name, assertion) in [
+                    ("{start-half}", Assertion::NoWordBefore),
+                    ("{end-half}", Assertion::NoWordAfter),
+                ] {
+                    if self.chars[self.pos..].starts_with(&name.chars().collect::<Vec<_>>()) {
+                        self.pos += name.len();
+                        return Ok(Node::Assert(assertion));
+                    }
+                }
+                Ok(Node::Assert(Assertion::WordBoundary))
+            }
+            'B' => Ok(Node::Assert(Assertion::NotWordBoundary)),
+            'n' => Ok(literal('\n')),
+            't' => Ok(literal('\t')),
+            'r' => Ok(literal('\r')),
+            'f' => Ok(literal('\x0c')),
+            'v' => Ok(literal('\x0b')),
+            '0' => Ok(literal('\0')),
+            'x' => {
+                let digits: String = (0..2).filter_map(|_| self.next()).collect();
+                // Checked first, as `from_str_radix` would also take a sign.
+                let hex = digits.len() == 2 && digits.chars().all(|c| c.is_ascii_hexdigit());
+                match u8::from_str_radix(&digits, 16) {
+                    Ok(byte) if hex => Ok(literal(char::from(byte))),
+                    _ => self.error("invalid hexadecimal escape"),
+                }
+            }
+            c if c.is_alphanumeric() => {
+                self.pos -= 1;
+                self.error("unrecognized escape sequence")
+            }
+            c => Ok(literal(c)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Inst {
+    Char(char, bool),
+    Any,
+    Class(Class),
+    Assert(Assertion),
+    Save(usize),
+    Split(usize, usize),
+    Jmp(usize),
+    Match,
+}
+
+struct Compiler {
+    program: Vec<Inst>,
+}
+
+impl Compiler {
+    fn compile(node: &Node) -> Result<Vec<Inst>, RegexError> {
+        let mut compiler = Compiler {
+            program: Vec::new(),
+        };
+        compiler.program.push(Inst::Save(0));
+        compiler.emit(node)?;
+        compiler.program.push(Inst::Save(1));
+        compiler.program.push(Inst::Match);
+        Ok(compiler.program)
+    }
+
+    fn pc(&self) -> usize {
+        self.program.len()
+    }
+
+    fn push(&mut self, inst: Inst) -> usize {
+        self.program.push(inst);
+        self.program.len() - 1
+    }
+
+    fn patch_split(&mut self, at: usize, first: usize, second: usize) {
+        self.program[at] = Inst::Split(first, second);
+    }
+
+    // Each call adds only a few instructions of its own, so checking the
+    // size on the way in keeps the program close to the limit.
+    fn emit(&mut self, node: &Node) -> Result<(), RegexError> {
+        if self.program.len() > MAX_PROGRAM {
+            return Err(RegexError {
+                message: String::from("pattern too large once repetitions are expanded"),
+                position: 0,
+            });
+        }
+
+        match node {
+            Node::Empty => {}
+            Node::Literal(c, ci) => {
+                self.push(Inst::Char(*c, *ci));
+            }
+            Node::Any => {
+                self.push(Inst::Any);
+            }
+            Node::Class(class) => {
+                self.push(Inst::Class(class.clone()));
+            }
+            Node::Assert(assertion) => {
+                self.push(Inst::Assert(*assertion));
+            }
+            Node::Group(node, index) => match index {
+                Some(index) => {
+                    self.push(Inst::Save(index * 2));
+                    self.emit(node)?;
+                    self.push(Inst::Save(index * 2 + 1));
+                }
+                None => self.emit(node)?,
+            },
+            Node::Concat(nodes) => {
+                for node in nodes {
+                    self.emit(node)?;
+                }
+            }
+            Node::Alternate(branches) => {
+                let mut jumps = Vec::new();
+                for (i, branch) in branches.iter().enumerate() {
+                    if i + 1 < branches.len() {
+                        let split = self.push(Inst::Split(0, 0));
+                        self.emit(branch)?;
+                        jumps.push(self.push(Inst::Jmp(0)));
+                        let next = self.pc();
+                        self.patch_split(split, split + 1, next);
+                    } else {
+                        self.emit(branch)?;
+                    }
+                }
+                let end = self.pc();
+                for jump in jumps {
+                    self.program[jump] = Inst::Jmp(end);
+                }
+            }
+            Node::Repeat {
+                node,
+                min,
+                max,
+                greedy,
+            } => {
+                for _ in 0..*min {
+                    self.emit(node)?;
+                }
+
+                match max {
+                    None => {
+                        let split = self.push(Inst::Split(0, 0));
+                        self.emit(node)?;
+                        self.push(Inst::Jmp(split));
+                        let end = self.pc();
+                        self.split(split, split + 1, end, *greedy);
+                    }
+                    Some(max) => {
+                        let mut splits = Vec::new();
+                        for _ in *min..*max {
+                            splits.push(self.push(Inst::Split(0, 0)));
+                            self.emit(node)?;
+                        }
+                        let end = self.pc();
+                        for split in splits {
+                            self.split(split, split + 1, end, *greedy);
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn split(&mut self, at: usize, body: usize, end: usize, greedy: bool) {
+        if greedy {
+            self.patch_split(at, body, end);
+        } else {
+            self.patch_split(at, end, body);
+        }
+    }
+}
+
+/// A compiled regular expression.
+#[derive(Debug, Clone)]
+pub struct Regex {
+    pattern: String,
+    program: Vec<Inst>,
+    groups: usize,
+}
+
+/// The positions of the groups captured by a single match. Group 0 is the
+/// whole match.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Captures {
+    slots: Vec<Option<usize>>,
+}
+
+impl Captures {
+    pub fn get(&self, group: usize) -> Option<Range<usize>> {
+        match (self.slots.get(group * 2), self.slots.get(group * 2 + 1)) {
+            (Some(Some(start)), Some(Some(end))) => Some(*start..*end),
+            _ => None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len() / 2
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Regex, RegexError> {
+        Regex::build(pattern, false)
+    }
+
+    pub fn new_case_insensitive(pattern: &str) -> Result<Regex, RegexError> {
+        Regex::build(pattern, true)
+    }
+
+    fn build(pattern: &str, case_insensitive: bool) -> Result<Regex, RegexError> {
+        let mut parser = Parser::new(pattern);
+        let node = parser.parse(Flags { case_insensitive })?;
+
+        Ok(Regex {
+            pattern: pattern.to_string(),
+            program: Compiler::compile(&node)?,
+            groups: parser.groups,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Number of capture groups, including the implicit group 0.
+    pub fn groups(&self) -> usize {
+        self.groups + 1
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.find(text).is_some()
+    }
+
+    pub fn find(&self, text: &str) -> Option<Range<usize>> {
+        self.find_at(text, 0)
+    }
+
+    pub fn find_at(&self, text: &str, start: usize) -> Option<Range<usize>> {
+        self.captures_at(text, start).and_then(|caps| caps.get(0))
+    }
+
+    pub fn captures(&self, text: &str) -> Option<Captures> {
+        self.captures_at(text, 0)
+    }
+
+    pub fn captures_at(&self, text: &str, start: usize) -> Option<Captures> {
+        self.execute(text, start).map(|slots| Captures { slots })
+    }
+
+    /// Iterates over every non-overlapping match in `text`.
+    pub fn find_iter<'r, 't>(&'r self, text: &'t str) -> FindIter<'r, 't> {
+        FindIter {
+            regex: self,
+            text,
+            pos: 0,
+            last_end: None,
+        }
+    }
+
+    fn execute(&self, text: &str, start: usize) -> Option<Vec<Option<usize>>> {
+        let slots = self.groups() * 2;
+        let mut current = Threads::new(self.program.len());
+        let mut next = Threads::new(self.program.len());
+        let mut matched = None;
+        let mut pos = start;
+
+        loop {
+            if matched.is_none() {
+                self.add_thread(&mut current, 0, vec![None; slots], text, pos);
+            }
+            if current.list.is_empty() && matched.is_some() {
+                break;
+            }
+
+            let c = text[pos..].chars().next();
+            let next_pos = pos + c.map_or(0, |c| c.len_utf8());
+
+            for (pc, caps) in current.list.drain(..) {
+                let step = match (&self.program[pc], c) {
+                    (Inst::Match, _) => {
+                        matched = Some(caps);
+                        break;
+                    }
+                    (Inst::Char(expected, ci), Some(c)) => fold(c, *ci) == *expected,
+                    (Inst::Any, Some(c)) => c != '\n',
+                    (Inst::Class(class), Some(c)) => class.matches(c),
+                    _ => false,
+                };
+                if step {
+                    self.add_thread(&mut next, pc + 1, caps, text, next_pos);
+                }
+            }
+
+            if c.is_none() {
+                break;
+            }
+            pos = next_pos;
+            std::mem::swap(&mut current, &mut next);
+            next.clear();
+        }
+
+        matched
+    }
+
+    // Follows every empty transition reachable from `pc`, in priority order,
+    // and queues the instructions that consume input.
+    fn add_thread(
+        &self,
+        threads: &mut Threads,
+        pc: usize,
+        caps: Vec<Option<usize>>,
+        text: &str,
+        pos: usize,
+    ) {
+        let mut stack = vec![(pc, caps)];
+
+        while let Some((pc, mut caps)) = stack.pop() {
+            if !threads.visit(pc) {
+                continue;
+            }
+            match &self.program[pc] {
+                Inst::Jmp(target) => stack.push((*target, caps)),
+                Inst::Split(first, second) => {
+                    stack.push((*second, caps.clone()));
+                    stack.push((*first, caps));
+                }
+                Inst::Save(slot) => {
+                    caps[*slot] = Some(pos);
+                    stack.push((pc + 1, caps));
+                }
+                Inst::Assert(assertion) => {
+                    if check_assertion(*assertion, text, pos) {
+                        stack.push((pc + 1, caps));
+                    }
+                }
+                _ => threads.list.push((pc, caps)),
+            }
+        }
+    }
+}
+
+impl fmt::Display for Regex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.pattern)
+    }
+}
+
+pub struct FindIter<'r, 't> {
+    regex: &'r Regex,
+    text: &'t str,
+    pos: usize,
+    last_end: Option<usize>,
+}
+
+impl<'r, 't> Iterator for FindIter<'r, 't> {
+    type Item = Range<usize>;
+
+    fn next(&mut self) -> Option<Range<usize>> {
+        loop {
+            if self.pos > self.text.len() {
+                return None;
+            }
+            let found = self.regex.find_at(self.text, self.pos)?;
+
+            if found.is_empty() && Some(found.end) == self.last_end {
+                // Skip an empty match right after the previous match.
+                self.pos = next_char_boundary(self.text, found.end);
+                continue;
+            }
+
+            self.pos = if found.is_empty() {
+                next_char_boundary(self.text, found.end)
+            } else {
+                found.end
+            };
+            self.last_end = Some(found.end);
+            return Some(found);
+        }
+    }
+}
+
+struct Threads {
+    list: Vec<(usize, Vec<Option<usize>>)>,
+    visited: Vec<bool>,
+}
+
+impl Threads {
+    fn new(size: usize) -> Threads {
+        Threads {
+            list: Vec::new(),
+            visited: vec![false; size],
+        }
+    }
+
+    fn visit(&mut self, pc: usize) -> bool {
+        !std::mem::replace(&mut self.visited[pc], true)
+    }
+
+    fn clear(&mut self) {
+        self.list.clear();
+        for visited in self.visited.iter_mut() {
+            *visited = false;
+        }
+    }
+}
+
+fn check_assertion(assertion: Assertion, text: &str, pos: usize) -> bool {
+    match assertion {
+        Assertion::Start => pos == 0 || text[..pos].ends_with('\n'),
+        Assertion::End => {
+            let rest = &text[pos..];
+            rest.is_empty() || rest.starts_with('\n') || rest.starts_with("\r\n")
+        }
+        Assertion::WordBoundary | Assertion::NotWordBoundary => {
+            let before = text[..pos].chars().next_back().is_some_and(is_word_char);
+            let after = text[pos..].chars().next().is_some_and(is_word_char);
+            (before != after) == (assertion == Assertion::WordBoundary)
+        }
+        Assertion::NoWordBefore => !text[..pos].chars().next_back().is_some_and(is_word_char),
+        Assertion::NoWordAfter => !text[pos..].chars().next().is_some_and(is_word_char),
+    }
+}
+
+fn next_char_boundary(text: &str, pos: usize) -> usize {
+    pos + text[pos..].chars().next().map_or(1, |c| c.len_utf8())
+}
+
+fn fold(c: char, case_insensitive: bool) -> char {
+    if case_insensitive {
+        case::fold_simple(c)
+    } else {
+        c
+    }
+}
+
+/// Word characters are letters, digits and connector punctuation like `_`.
+pub fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find<'a>(pattern: &str, text: &'a str) -> Option<&'a str> {
+        Regex::new(pattern).unwrap().find(text).map(|m| &text[m])
+    }
+
+    #[test]
+    fn literals_and_classes() {
+        assert_eq!(Some("fn main"), find(r"^fn\s+\w+", "fn main() {"));
+        assert_eq!(None, find(r"^fn\s+\w+", "  fn main() {"));
+        assert_eq!(
+            Some("555-1234"),
+            find("[0-9]{3}-[0-9]{4}", "call 555-1234 now")
+        );
+        assert_eq!(Some("b"), find("[^a]", "aab"));
+        assert_eq!(Some("x-"), find(r"[\w-]+", "x-"));
+    }
+
+    #[test]
+    fn alternation_and_repetition() {
+        assert_eq!(Some("cat"), find("dog|cat", "a cat"));
+        assert_eq!(Some("aaa"), find("a+", "baaa"));
+        assert_eq!(Some("a"), find("a+?", "aaa"));
+        assert_eq!(Some("colour"), find("colou?r", "colour"));
+        assert_eq!(Some("abab"), find("(ab){2,}", "ababx"));
+        assert_eq!(Some(""), find("x*", "abc"));
+    }
+
+    #[test]
+    fn anchors_and_word_boundaries() {
+        assert_eq!(Some("end"), find("end$", "the end"));
+        assert_eq!(None, find("end$", "ending"));
+        assert_eq!(Some("b"), find("^b$", "a\nb\r\nc"));
+        assert_eq!(None, find("^b$", "ab\nbc"));
+        assert_eq!(Some("id"), find(r"\bid\b", "an id here"));
+        assert_eq!(None, find(r"\bid\b", "width"));
+        assert_eq!(Some("-id"), find(r"\b{start-half}-id\b{end-half}", "x -id"));
+        assert_eq!(None, find(r"\b{start-half}-id\b{end-half}", "x-id"));
+    }
+
+    #[test]
+    fn capture_groups() {
+        let re = Regex::new(r"(\w+)@(\w+)\.com").unwrap();
+        let text = "mail bob@example.com";
+        let caps = re.captures(text).unwrap();
+
+        assert_eq!(3, caps.len());
+        assert_eq!("bob@example.com", &text[caps.get(0).unwrap()]);
+        assert_eq!("bob", &text[caps.get(1).unwrap()]);
+        assert_eq!("example", &text[caps.get(2).unwrap()]);
+    }
+
+    #[test]
+    fn case_insensitive_flags() {
+        let re = Regex::new_case_insensitive("rust").unwrap();
+        assert!(re.is_match("Trust me."));
+        assert_eq!(Some("RuSt"), find("(?i)rust", "RuSt"));
+        assert_eq!(Some("ÉTÉ"), find("(?i)[é]t[é]", "ÉTÉ"));
+        assert_eq!(Some("ΣΟΦΟΣ"), find("(?i)σοφος", "ΣΟΦΟΣ"));
+        assert_eq!(Some("ς"), find("(?i)[σ]", "ς"));
+    }
+
+    #[test]
+    fn find_iter_handles_empty_matches() {
+        let re = Regex::new("a*").unwrap();
+        let found: Vec<_> = re.find_iter("baac").collect();
+        assert_eq!(vec![0..0, 1..3, 4..4], found);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(Regex::new("(abc").is_err());
+        assert!(Regex::new("abc)").is_err());
+        assert!(Regex::new("[abc").is_err());
+        assert!(Regex::new("*a").is_err());
+        assert!(Regex::new(r"\q").is_err());
+        assert!(Regex::new(r"\x+1").is_err());
+        assert!(Regex::new(r"\x4").is_err());
+        assert_eq!(Some("A"), find(r"\x41", "zA"));
+        assert_eq!(1, Regex::new("a)").unwrap_err().position);
+    }
+
+    #[test]
+    fn limits_expanded_repetition() {
+        assert_eq!(
+            "regex parse error at position 0: pattern too large once repetitions are expanded",
+            Regex::new("(a{1000}){1000}").unwrap_err().to_string()
+        );
+        assert!(Regex::new("(a{1000}){50}").is_ok());
+    }
+}
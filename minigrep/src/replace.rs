@@ -0,0 +1,380 @@
+use std::borrow::Cow;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::mem;
+use std::ops::Range;
+use std::path::Path;
+use std::process;
+
+use crate::matcher::Matcher;
+
+// Rewrites occurrences for `--replace`. With a regular expression the
+// replacement can refer to capture groups as `$1` or `${1}`; `$0` is the
+// whole occurrence and `$$` a literal dollar sign. Files are rewritten line
+// by line, so their line endings are left alone.
+
+// Lines of unchanged context around each hunk of a diff.
+const DIFF_CONTEXT: usize = 3;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Piece {
+    Literal(String),
+    Group(usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct Replacer {
+    pieces: Vec<Piece>,
+    matcher: Matcher,
+}
+
+impl Replacer {
+    pub fn new(replacement: &str, matcher: &Matcher) -> Replacer {
+        Replacer {
+            pieces: parse(replacement),
+            matcher: matcher.clone(),
+        }
+    }
+
+    /// Replaces the occurrences at `ranges` in `line`. Returns the new line
+    /// along with the ranges of the text put in.
+    pub fn replace(&self, line: &str, ranges: &[Range<usize>]) -> (String, Vec<Range<usize>>) {
+        let mut replaced = String::with_capacity(line.len());
+        let mut inserted = Vec::with_capacity(ranges.len());
+        let mut last = 0;
+
+        for range in ranges {
+            replaced.push_str(&line[last..range.start]);
+            let start = replaced.len();
+            self.expand(line, range, &mut replaced);
+            inserted.push(start..replaced.len());
+            last = range.end;
+        }
+        replaced.push_str(&line[last..]);
+
+        (replaced, inserted)
+    }
+
+    fn expand(&self, line: &str, range: &Range<usize>, out: &mut String) {
+        let captures = self
+            .matcher
+            .regex()
+            .and_then(|regex| regex.captures_at(line, range.start))
+            .filter(|captures| captures.get(0).as_ref() == Some(range));
+
+        for piece in &self.pieces {
+            match piece {
+                Piece::Literal(text) => out.push_str(text),
+                Piece::Group(0) => out.push_str(&line[range.clone()]),
+                // Groups that did not take part in the match, or do not
+                // exist, expand to nothing.
+                Piece::Group(group) => {
+                    if let Some(group) = captures.as_ref().and_then(|c| c.get(*group)) {
+                        out.push_str(&line[group]);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Works out the replacement for every line of `contents`.
+    pub fn rewrite<'a>(&self, contents: &'a str) -> Rewrite<'a> {
+        let lines = contents
+            .split_inclusive('\n')
+            .map(|line| {
+                let text = trim_line_ending(line);
+                let ranges = self.matcher.find_all(text);
+                if ranges.is_empty() {
+                    (line, Cow::Borrowed(line))
+                } else {
+                    let (replaced, _) = self.replace(text, &ranges);
+                    (line, Cow::Owned(replaced + &line[text.len()..]))
+                }
+            })
+            .collect();
+
+        Rewrite { lines }
+    }
+}
+
+fn parse(replacement: &str) -> Vec<Piece> {
+    let mut pieces = Vec::new();
+    let mut literal = String::new();
+    let mut rest = replacement;
+
+    while let Some(i) = rest.find('$') {
+        literal.push_str(&rest[..i]);
+        rest = &rest[i + 1..];
+
+        let (digits, after) = if let Some(braced) = rest.strip_prefix('{') {
+            match braced.find('}') {
+                Some(end) if is_number(&braced[..end]) => (&braced[..end], &braced[end + 1..]),
+                _ => ("", rest),
+            }
+        } else {
+            let end = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            (&rest[..end], &rest[end..])
+        };
+
+        if let Ok(group) = digits.parse() {
+            if !literal.is_empty() {
+                pieces.push(Piece::Literal(mem::take(&mut literal)));
+            }
+            pieces.push(Piece::Group(group));
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix('$') {
+            literal.push('$');
+            rest = after;
+        } else {
+            literal.push('$');
+        }
+    }
+
+    literal.push_str(rest);
+    if !literal.is_empty() {
+        pieces.push(Piece::Literal(literal));
+    }
+    pieces
+}
+
+fn is_number(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_digit())
+}
+
+fn trim_line_ending(line: &str) -> &str {
+    match line.strip_suffix('\n') {
+        Some(line) => line.strip_suffix('\r').unwrap_or(line),
+        None => line,
+    }
+}
+
+/// Every line of a file, line ending included, along with what it becomes.
+pub struct Rewrite<'a> {
+    lines: Vec<(&'a str, Cow<'a, str>)>,
+}
+
+impl<'a> Rewrite<'a> {
+    /// The number of lines that change.
+    pub fn changed_lines(&self) -> usize {
+        self.lines.iter().filter(|(old, new)| old != new).count()
+    }
+
+    /// The rewritten file.
+    pub fn contents(&self) -> String {
+        self.lines.iter().map(|(_, new)| new.as_ref()).collect()
+    }
+
+    /// Writes the changes as a unified diff, like `diff -u` would.
+    pub fn write_diff<W: Write>(&self, out: &mut W, path: &Path) -> io::Result<()> {
+        let changed: Vec<usize> = (0..self.lines.len())
+            .filter(|&i| self.lines[i].0 != self.lines[i].1)
+            .collect();
+        if changed.is_empty() {
+            return Ok(());
+        }
+
+        writeln!(out, "--- {}", path.display())?;
+        writeln!(out, "+++ {}", path.display())?;
+
+        let mut i = 0;
+        while i < changed.len() {
+            // Hunks whose context would touch or overlap are merged.
+            let mut last = i;
+            while last + 1 < changed.len()
+                && changed[last + 1] - changed[last] <= 2 * DIFF_CONTEXT + 1
+            {
+                last += 1;
+            }
+
+            let start = changed[i].saturating_sub(DIFF_CONTEXT);
+            let end = (changed[last] + DIFF_CONTEXT + 1).min(self.lines.len());
+            self.write_hunk(out, start..end)?;
+            i = last + 1;
+        }
+
+        Ok(())
+    }
+
+    fn write_hunk<W: Write>(&self, out: &mut W, hunk: Range<usize>) -> io::Result<()> {
+        let new_start = 1 + self.lines[..hunk.start]
+            .iter()
+            .map(|(_, new)| line_count(new))
+            .sum::<usize>();
+        let new_count: usize = self.lines[hunk.clone()]
+            .iter()
+            .map(|(_, new)| line_count(new))
+            .sum();
+
+        writeln!(
+            out,
+            "@@ -{},{} +{},{} @@",
+            hunk.start + 1,
+            hunk.len(),
+            new_start,
+            new_count
+        )?;
+
+        let mut lines = &self.lines[hunk];
+        while let Some((old, new)) = lines.first() {
+            if old == new {
+                write_diff_line(out, ' ', old)?;
+                lines = &lines[1..];
+                continue;
+            }
+
+            // A run of changed lines is shown as all of its removals
+            // followed by all of its additions.
+            let run = lines
+                .iter()
+                .position(|(old, new)| old == new)
+                .unwrap_or(lines.len());
+            for (old, _) in &lines[..run] {
+                write_diff_line(out, '-', old)?;
+            }
+            for line in lines[..run].iter().flat_map(|(_, new)| new.split_inclusive('\n')) {
+                write_diff_line(out, '+', line)?;
+            }
+            lines = &lines[run..];
+        }
+
+        Ok(())
+    }
+}
+
+fn line_count(text: &str) -> usize {
+    text.split_inclusive('\n').count()
+}
+
+fn write_diff_line<W: Write>(out: &mut W, prefix: char, line: &str) -> io::Result<()> {
+    write!(out, "{}{}", prefix, line)?;
+    if !line.ends_with('\n') {
+        writeln!(out)?;
+        writeln!(out, "\\ No newline at end of file")?;
+    }
+    Ok(())
+}
+
+/// Replaces the file at `path` with `contents` by writing them to a
+/// temporary file next to it and renaming that over the original, so the
+/// file is never left half written. A symbolic link is followed, so the
+/// file it points to is replaced and the link stays a link.
+pub fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let path = &fs::canonicalize(path)?;
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file"))?;
+    let temp = path.with_file_name(format!(
+        ".{}.minigrep-{}",
+        name.to_string_lossy(),
+        process::id()
+    ));
+
+    let result = File::create(&temp)
+        .and_then(|mut file| {
+            file.write_all(contents)?;
+            file.sync_all()
+        })
+        .and_then(|_| fs::set_permissions(&temp, fs::metadata(path)?.permissions()))
+        .and_then(|_| fs::rename(&temp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::regex::Regex;
+
+    fn replacer(pattern: &str, replacement: &str) -> Replacer {
+        Replacer::new(replacement, &Matcher::Regex(Regex::new(pattern).unwrap()))
+    }
+
+    #[test]
+    fn parses_group_references() {
+        assert_eq!(
+            vec![
+                Piece::Group(2),
+                Piece::Literal("-".to_string()),
+                Piece::Group(10),
+                Piece::Literal("x $ $y ${a}".to_string()),
+            ],
+            parse("$2-${10}x $$ $y ${a}")
+        );
+    }
+
+    #[test]
+    fn replaces_with_captures() {
+        let replacer = replacer(r"(\w+)@(\w+)", "$2 at ${1}");
+        let line = "mail bob@home and amy@work";
+        let ranges = replacer.matcher.find_all(line);
+
+        let (replaced, inserted) = replacer.replace(line, &ranges);
+        assert_eq!("mail home at bob and work at amy", replaced);
+        assert_eq!(vec![5..16, 21..32], inserted);
+    }
+
+    #[test]
+    fn literal_matchers_only_have_the_whole_match() {
+        let replacer = Replacer::new("[$0$1]", &Matcher::literal("to"));
+        let (replaced, _) = replacer.replace("go to it to", &[3..5, 9..11]);
+        assert_eq!("go [to] it [to]", replaced);
+    }
+
+    #[test]
+    fn writes_unified_diff() {
+        let contents = "a\nfoo\nb\nc\nd\ne\nf\ng\nh\ni\nfoo\r\nj";
+        let rewrite = replacer("foo", "bar\nbaz").rewrite(contents);
+        assert_eq!(2, rewrite.changed_lines());
+        assert_eq!(
+            "a\nbar\nbaz\nb\nc\nd\ne\nf\ng\nh\ni\nbar\nbaz\r\nj",
+            rewrite.contents()
+        );
+
+        let mut diff = Vec::new();
+        rewrite.write_diff(&mut diff, Path::new("x.txt")).unwrap();
+        assert_eq!(
+            "--- x.txt\n+++ x.txt\n\
+             @@ -1,5 +1,6 @@\n a\n-foo\n+bar\n+baz\n b\n c\n d\n\
+             @@ -8,5 +9,6 @@\n g\n h\n i\n-foo\r\n+bar\n+baz\r\n j\n\\ No newline at end of file\n",
+            String::from_utf8(diff).unwrap()
+        );
+    }
+
+    #[test]
+    fn rewrites_files_atomically() {
+        let path = std::env::temp_dir().join(format!("minigrep-replace-{}", process::id()));
+        fs::write(&path, "old").unwrap();
+
+        write_atomically(&path, b"new").unwrap();
+        assert_eq!("new", fs::read_to_string(&path).unwrap());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rewrites_the_target_of_a_link() {
+        use std::os::unix::fs::symlink;
+
+        let dir = std::env::temp_dir().join(format!("minigrep-replace-link-{}", process::id()));
+        fs::create_dir_all(dir.join("d")).unwrap();
+        fs::write(dir.join("real.txt"), "old").unwrap();
+        symlink("../real.txt", dir.join("d/link.txt")).unwrap();
+
+        write_atomically(&dir.join("d/link.txt"), b"new").unwrap();
+        assert_eq!("new", fs::read_to_string(dir.join("real.txt")).unwrap());
+        let link = fs::symlink_metadata(dir.join("d/link.txt")).unwrap();
+        assert!(link.file_type().is_symlink());
+        assert_eq!(
+            vec!["link.txt"],
+            fs::read_dir(dir.join("d"))
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name())
+                .collect::<Vec<_>>()
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
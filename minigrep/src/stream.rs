@@ -0,0 +1,493 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::io::{self, BufRead};
+use std::ops::Range;
+
+use crate::context::{Context, ContextLine};
+use crate::matcher::Matcher;
+use crate::memmem::{self, Finder};
+
+// Searches input read incrementally from a buffered reader. Only the
+// current line and the requested before-context are held in memory, so
+// the size of the input does not matter.
+//
+// Input with a NUL byte is taken to be binary: from the first NUL on,
+// selected lines are counted but not passed on, since printing them would
+// only garble the terminal. Any other bytes that are not valid UTF-8 are
+// searched and passed on with U+FFFD in their place.
+//
+// With `multiline` set, the whole input is read first and the matcher run
+// over all of it, so occurrences may span lines. Every line an occurrence
+// touches is then selected, with the part of the occurrence on that line
+// as its range.
+
+// How much of the input is checked up front for a NUL byte, as much as a
+// `BufReader` holds at first.
+const BINARY_SNIFF_LEN: usize = 8 * 1024;
+
+// How much input looking for the needle ahead has to pass over to be
+// worth doing, and the most lines searched one by one before trying again
+// when it does not.
+const MIN_SKIP_LEN: usize = 1024;
+const MAX_SKIP_BACKOFF: usize = 64;
+
+/// What a search found.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Outcome {
+    /// The number of selected lines.
+    pub count: usize,
+    /// Whether selected lines were withheld because the input is binary.
+    pub binary_match: bool,
+}
+
+pub struct StreamSearcher<'m> {
+    matcher: &'m Matcher,
+    pub before_context: usize,
+    pub after_context: usize,
+    /// Select the lines that do not match instead.
+    pub invert_match: bool,
+    /// Stop reading after this many selected lines and their trailing
+    /// context.
+    pub max_count: Option<usize>,
+    /// Pass on every line, even from binary input.
+    pub text: bool,
+    /// Let occurrences span lines.
+    pub multiline: bool,
+}
+
+impl<'m> StreamSearcher<'m> {
+    pub fn new(matcher: &'m Matcher) -> StreamSearcher<'m> {
+        StreamSearcher {
+            matcher,
+            before_context: 0,
+            after_context: 0,
+            invert_match: false,
+            max_count: None,
+            text: false,
+            multiline: false,
+        }
+    }
+
+    pub fn matcher(&self) -> &'m Matcher {
+        self.matcher
+    }
+
+    /// Reads `reader` line by line and passes every line to print to `emit`.
+    pub fn search<R, F>(&self, mut reader: R, mut emit: F) -> io::Result<Outcome>
+    where
+        R: BufRead,
+        F: FnMut(ContextLine) -> io::Result<()>,
+    {
+        if self.multiline {
+            return self.search_multiline(reader, emit);
+        }
+
+        let mut context = Context::new(self.before_context, self.after_context);
+        let mut buffer = Vec::new();
+        let mut line_number = 0;
+        let mut byte_offset = 0;
+        let mut outcome = Outcome::default();
+        // Binary files usually give themselves away in their first block.
+        let mut binary = !self.text && reader.fill_buf()?.contains(&0);
+        // Lines without the finder's needle can neither be selected nor,
+        // without before-context, printed, so they are passed over in bulk.
+        let skip_with = self
+            .matcher
+            .required_finder()
+            .filter(|_| !self.invert_match && self.before_context == 0);
+        // Where the needle is on most lines there is little to skip, and
+        // looking for it first only slows the search down. Each time
+        // little is skipped, the search goes line by line for twice as
+        // long before trying again.
+        let mut skip_from = 0;
+        let mut backoff = 1;
+
+        loop {
+            let limit_reached = self.max_count.is_some_and(|max| outcome.count >= max);
+            if limit_reached && !context.in_after_context() {
+                break;
+            }
+
+            if let Some(finder) = skip_with {
+                if line_number >= skip_from && !context.in_after_context() {
+                    let skipped = skip_lines(&mut reader, finder)?;
+                    line_number += skipped.lines;
+                    byte_offset += skipped.bytes;
+                    binary = binary || (!self.text && skipped.nul);
+                    if skipped.bytes < MIN_SKIP_LEN {
+                        skip_from = line_number + backoff;
+                        backoff = (backoff * 2).min(MAX_SKIP_BACKOFF);
+                    } else {
+                        backoff = 1;
+                    }
+                }
+            }
+
+            buffer.clear();
+            let read = reader.read_until(b'\n', &mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            line_number += 1;
+
+            binary = binary || (!self.text && buffer.contains(&0));
+
+            let line: Cow<str> = String::from_utf8_lossy(trim_line_ending(&buffer));
+            let mut ranges = self.matcher.find_all(&line);
+            let selected = !limit_reached && ranges.is_empty() == self.invert_match;
+            if !selected || self.invert_match {
+                ranges.clear();
+            }
+            if selected {
+                outcome.count += 1;
+                outcome.binary_match = outcome.binary_match || binary;
+            }
+
+            if !binary {
+                context.push(line_number, byte_offset, &line, selected, ranges, &mut emit)?;
+            }
+            byte_offset += read;
+        }
+
+        Ok(outcome)
+    }
+
+    fn search_multiline<R, F>(&self, mut reader: R, mut emit: F) -> io::Result<Outcome>
+    where
+        R: BufRead,
+        F: FnMut(ContextLine) -> io::Result<()>,
+    {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        let contents = String::from_utf8_lossy(&bytes);
+
+        // Each line, line ending included, by where it starts.
+        let lines: Vec<Range<usize>> = contents
+            .split_inclusive('\n')
+            .scan(0, |offset, line| {
+                let start = *offset;
+                *offset += line.len();
+                Some(start..*offset)
+            })
+            .collect();
+        let mut ranges = touched_lines(&lines, &self.matcher.find_all(&contents));
+
+        let mut context = Context::new(self.before_context, self.after_context);
+        let mut outcome = Outcome::default();
+        let sniffed = &bytes[..bytes.len().min(BINARY_SNIFF_LEN)];
+        let mut binary = !self.text && sniffed.contains(&0);
+
+        for (i, span) in lines.iter().enumerate() {
+            let limit_reached = self.max_count.is_some_and(|max| outcome.count >= max);
+            if limit_reached && !context.in_after_context() {
+                break;
+            }
+
+            let line = trim_line_ending(contents[span.clone()].as_bytes());
+            let line = &contents[span.start..span.start + line.len()];
+            binary = binary || (!self.text && line.contains('\0'));
+
+            let found = ranges.remove(&i);
+            let selected = !limit_reached && found.is_some() != self.invert_match;
+            // Leave out the line ending, which an occurrence may cover.
+            let found = match found {
+                Some(found) if selected && !self.invert_match => found
+                    .into_iter()
+                    .map(|range| range.start.min(line.len())..range.end.min(line.len()))
+                    .filter(|range| !range.is_empty())
+                    .collect(),
+                _ => Vec::new(),
+            };
+            if selected {
+                outcome.count += 1;
+                outcome.binary_match = outcome.binary_match || binary;
+            }
+
+            if !binary {
+                context.push(i + 1, span.start, line, selected, found, &mut emit)?;
+            }
+        }
+
+        Ok(outcome)
+    }
+}
+
+// Lines passed over by `skip_lines`.
+#[derive(Debug, Default, PartialEq)]
+struct Skipped {
+    lines: usize,
+    bytes: usize,
+    nul: bool,
+}
+
+// Consumes the whole lines in the reader's buffer up to the first one with
+// an occurrence of the needle. With no occurrence, every whole line in the
+// buffer goes, and the next call looks at a fresh buffer. A line that does
+// not end in the buffer is left alone, as the rest of it may hold one.
+fn skip_lines<R: BufRead>(reader: &mut R, finder: &Finder) -> io::Result<Skipped> {
+    let mut skipped = Skipped::default();
+
+    loop {
+        let block = reader.fill_buf()?;
+        let found = finder.find_at(block, 0);
+        let end = match block[..found.unwrap_or(block.len())]
+            .iter()
+            .rposition(|&b| b == b'\n')
+        {
+            Some(newline) => newline + 1,
+            None => return Ok(skipped),
+        };
+
+        let lines = &block[..end];
+        skipped.lines += memmem::count(b'\n', lines);
+        skipped.bytes += end;
+        skipped.nul = skipped.nul || memmem::memchr(0, lines).is_some();
+        reader.consume(end);
+        if found.is_some() {
+            return Ok(skipped);
+        }
+    }
+}
+
+// The lines each occurrence touches, by index into `lines`, along with the
+// part of the occurrence on each, relative to the start of its line. An
+// occurrence that ends with a line ending does not touch the next line.
+fn touched_lines(
+    lines: &[Range<usize>],
+    occurrences: &[Range<usize>],
+) -> BTreeMap<usize, Vec<Range<usize>>> {
+    let mut touched: BTreeMap<usize, Vec<Range<usize>>> = BTreeMap::new();
+
+    for occurrence in occurrences {
+        let first = lines.partition_point(|line| line.end <= occurrence.start);
+        for (i, line) in lines.iter().enumerate().skip(first) {
+            if i > first && line.start >= occurrence.end {
+                break;
+            }
+            let start = occurrence.start.max(line.start);
+            let end = occurrence.end.min(line.end);
+            let ranges = touched.entry(i).or_default();
+            if start < end {
+                ranges.push(start - line.start..end - line.start);
+            }
+        }
+    }
+
+    touched
+}
+
+fn trim_line_ending(line: &[u8]) -> &[u8] {
+    match line.strip_suffix(b"\n") {
+        Some(line) => line.strip_suffix(b"\r").unwrap_or(line),
+        None => line,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::regex::Regex;
+    use std::io::{BufReader, Cursor};
+
+    #[test]
+    fn streams_matches_with_positions() {
+        let matcher = Matcher::literal("duct");
+        let contents = "Rust:\r\nsafe, fast, productive.\nPick three.\nDuct tape.";
+        // A tiny buffer makes sure lines spanning several reads work.
+        let reader = BufReader::with_capacity(4, Cursor::new(contents));
+
+        let mut searcher = StreamSearcher::new(&matcher);
+        searcher.before_context = 1;
+
+        let mut output = Vec::new();
+        searcher
+            .search(reader, |line| {
+                output.push(match line {
+                    ContextLine::Match(found) => format!(
+                        "{}:{}:{}:{:?}",
+                        found.line_number, found.byte_offset, found.line, found.ranges
+                    ),
+                    ContextLine::Context {
+                        line_number,
+                        byte_offset,
+                        line,
+                    } => format!("{}-{}-{}", line_number, byte_offset, line),
+                    ContextLine::Separator => "--".to_string(),
+                });
+                Ok(())
+            })
+            .unwrap();
+
+        assert_eq!(
+            vec!["1-0-Rust:", "2:7:safe, fast, productive.:[15..19]"],
+            output
+        );
+    }
+
+    fn selected_lines<T: AsRef<[u8]>>(
+        searcher: &StreamSearcher,
+        contents: T,
+    ) -> (Outcome, Vec<String>) {
+        let mut output = Vec::new();
+        let outcome = searcher
+            .search(Cursor::new(contents), |line| {
+                if let ContextLine::Match(found) = line {
+                    output.push(found.line.to_string());
+                }
+                Ok(())
+            })
+            .unwrap();
+        (outcome, output)
+    }
+
+    #[test]
+    fn multiline_selects_every_line_touched() {
+        let matcher = Matcher::Regex(Regex::new(r"error:.*\n.*caused by").unwrap());
+        let mut searcher = StreamSearcher::new(&matcher);
+        searcher.multiline = true;
+
+        let contents = "ok\nerror: disk\r\n  caused by: full\nerror: net\nok\n";
+        let mut output = Vec::new();
+        let outcome = searcher
+            .search(Cursor::new(contents), |line| {
+                if let ContextLine::Match(found) = line {
+                    output.push(format!(
+                        "{}:{}:{:?}",
+                        found.line_number, found.line, found.ranges
+                    ));
+                }
+                Ok(())
+            })
+            .unwrap();
+
+        assert_eq!(2, outcome.count);
+        assert_eq!(
+            vec!["2:error: disk:[0..11]", "3:  caused by: full:[0..11]"],
+            output
+        );
+
+        let matcher = Matcher::Regex(Regex::new("^ok$").unwrap());
+        let mut searcher = StreamSearcher::new(&matcher);
+        searcher.multiline = true;
+        searcher.invert_match = true;
+        let (outcome, lines) = selected_lines(&searcher, contents);
+        assert_eq!(3, outcome.count);
+        assert_eq!(
+            vec!["error: disk", "  caused by: full", "error: net"],
+            lines
+        );
+    }
+
+    #[test]
+    fn skips_lines_without_the_needle() {
+        let matcher = Matcher::literal("needle");
+        let mut searcher = StreamSearcher::new(&matcher);
+        searcher.after_context = 1;
+        let contents = "hay\nhay\nhay needle\nafter\nhay\nlong hay line\n\0\nneedle";
+
+        for capacity in [4, 8, 16] {
+            let reader = BufReader::with_capacity(capacity, Cursor::new(contents));
+            let mut output = Vec::new();
+            let outcome = searcher
+                .search(reader, |line| {
+                    output.push(match line {
+                        ContextLine::Match(found) => {
+                            format!("{}:{}:{}", found.line_number, found.byte_offset, found.line)
+                        }
+                        ContextLine::Context {
+                            line_number,
+                            byte_offset,
+                            line,
+                        } => format!("{}-{}-{}", line_number, byte_offset, line),
+                        ContextLine::Separator => "--".to_string(),
+                    });
+                    Ok(())
+                })
+                .unwrap();
+
+            assert_eq!(2, outcome.count);
+            assert!(outcome.binary_match, "capacity {}", capacity);
+            assert_eq!(vec!["3:8:hay needle", "4-19-after"], output);
+        }
+
+        let mut skipped = Cursor::new("a\nb\nc needle\n");
+        assert_eq!(
+            Skipped {
+                lines: 2,
+                bytes: 4,
+                nul: false
+            },
+            skip_lines(&mut skipped, &Finder::new(b"needle")).unwrap()
+        );
+    }
+
+    #[test]
+    fn skips_ahead_again_after_dense_matches() {
+        let matcher = Matcher::literal("x");
+        let searcher = StreamSearcher::new(&matcher);
+        let mut contents = "x\n".repeat(300);
+        contents.push_str(&"y\n".repeat(2000));
+        contents.push_str("x last\n");
+
+        let mut found = Vec::new();
+        let outcome = searcher
+            .search(Cursor::new(contents), |line| {
+                if let ContextLine::Match(m) = line {
+                    found.push((m.line_number, m.byte_offset));
+                }
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(301, outcome.count);
+        assert_eq!(Some(&(299, 596)), found.get(298));
+        assert_eq!(Some(&(2301, 4600)), found.last());
+    }
+
+    #[test]
+    fn inverts_selection() {
+        let matcher = Matcher::literal("a");
+        let mut searcher = StreamSearcher::new(&matcher);
+        searcher.invert_match = true;
+
+        let (outcome, lines) = selected_lines(&searcher, "a\nb\nc\nab\n");
+        assert_eq!(2, outcome.count);
+        assert_eq!(vec!["b", "c"], lines);
+    }
+
+    #[test]
+    fn stops_after_max_count() {
+        let matcher = Matcher::literal("x");
+        let mut searcher = StreamSearcher::new(&matcher);
+        searcher.max_count = Some(2);
+
+        let (outcome, lines) = selected_lines(&searcher, "x1\ny\nx2\nx3\n");
+        assert_eq!(2, outcome.count);
+        assert_eq!(vec!["x1", "x2"], lines);
+    }
+
+    #[test]
+    fn searches_invalid_utf8_lossily() {
+        let matcher = Matcher::literal("a");
+        let searcher = StreamSearcher::new(&matcher);
+        let (outcome, lines) = selected_lines(&searcher, b"a\xff\nb\n");
+
+        assert_eq!(1, outcome.count);
+        assert!(!outcome.binary_match);
+        assert_eq!(vec!["a\u{fffd}"], lines);
+    }
+
+    #[test]
+    fn withholds_lines_from_binary_input() {
+        let matcher = Matcher::literal("a");
+        let mut searcher = StreamSearcher::new(&matcher);
+        let (outcome, lines) = selected_lines(&searcher, b"a\n\0\na\n");
+        assert_eq!(2, outcome.count);
+        assert!(outcome.binary_match);
+        assert!(lines.is_empty());
+
+        searcher.text = true;
+        let (outcome, lines) = selected_lines(&searcher, b"a\n\0\na\n");
+        assert!(!outcome.binary_match);
+        assert_eq!(vec!["a", "a"], lines);
+    }
+}
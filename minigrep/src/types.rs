@@ -0,0 +1,39 @@
+// Named sets of file name globs for `--type`.
+
+pub const TYPES: &[(&str, &[&str])] = &[
+    ("c", &["*.[ch]"]),
+    ("cpp", &["*.{cc,cpp,cxx,hh,hpp,hxx,h}"]),
+    ("css", &["*.{css,scss,sass,less}"]),
+    ("go", &["*.go"]),
+    ("html", &["*.{htm,html}"]),
+    ("java", &["*.java"]),
+    ("js", &["*.{js,jsx,mjs,cjs}"]),
+    ("json", &["*.json"]),
+    ("lock", &["*.lock", "package-lock.json"]),
+    ("make", &["Makefile", "makefile", "GNUmakefile", "*.mk"]),
+    ("md", &["*.{md,markdown}"]),
+    ("py", &["*.{py,pyi}"]),
+    ("rust", &["*.rs"]),
+    ("sh", &["*.{sh,bash,zsh}"]),
+    ("toml", &["*.toml"]),
+    ("ts", &["*.{ts,tsx,mts,cts}"]),
+    ("txt", &["*.txt"]),
+    ("yaml", &["*.{yml,yaml}"]),
+];
+
+/// The globs of the type called `name`.
+pub fn globs(name: &str) -> Option<&'static [&'static str]> {
+    TYPES
+        .iter()
+        .find(|(known, _)| *known == name)
+        .map(|(_, globs)| *globs)
+}
+
+/// The names of every type, for error messages.
+pub fn names() -> String {
+    TYPES
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -0,0 +1,386 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::error::MinigrepError;
+use crate::glob::{Glob, GlobError};
+use crate::types;
+
+// Collects the files to search from a list of paths. Directories are
+// walked recursively, skipping hidden entries and anything excluded by a
+// `.gitignore` or `.ignore` file found along the way. Paths given
+// explicitly are always searched.
+//
+// A `Filter` built from `--glob` and `--type` narrows the walk further.
+// Its globs work like ignore rules turned around: a match keeps an entry
+// and a match of a glob starting with `!` drops it, the last matching glob
+// deciding, whatever the ignore files say. Once there is a glob that keeps
+// entries, files no glob keeps are dropped; once there is a type, so are
+// files of no type asked for.
+
+const IGNORE_FILES: [&str; 2] = [".gitignore", ".ignore"];
+
+#[derive(Debug, Clone)]
+struct Rule {
+    glob: Glob,
+    negated: bool,
+    dir_only: bool,
+}
+
+// The rules read from the ignore files of a single directory.
+struct IgnoreDir {
+    root: PathBuf,
+    rules: Vec<Rule>,
+}
+
+impl IgnoreDir {
+    fn load(root: &Path) -> Option<IgnoreDir> {
+        let mut rules = Vec::new();
+
+        for name in IGNORE_FILES.iter() {
+            if let Ok(contents) = fs::read_to_string(root.join(name)) {
+                rules.extend(contents.lines().filter_map(parse_rule));
+            }
+        }
+
+        if rules.is_empty() {
+            None
+        } else {
+            Some(IgnoreDir {
+                root: root.to_path_buf(),
+                rules,
+            })
+        }
+    }
+
+    // The last matching rule wins. Returns None when no rule matches.
+    fn matched(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        let relative = path.strip_prefix(&self.root).ok()?;
+        let relative = relative.to_string_lossy().replace('\\', "/");
+
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| (is_dir || !rule.dir_only) && rule.glob.is_match(&relative))
+            .map(|rule| !rule.negated)
+    }
+}
+
+fn parse_rule(line: &str) -> Option<Rule> {
+    let line = line.trim_end();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    let (negated, line) = match line.strip_prefix('!') {
+        Some(rest) => (true, rest),
+        None => (false, line.strip_prefix('\\').unwrap_or(line)),
+    };
+    let (dir_only, line) = match line.strip_suffix('/') {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+
+    anchored(line).ok().map(|glob| Rule {
+        glob,
+        negated,
+        dir_only,
+    })
+}
+
+// A pattern with a slash is relative to the directory it applies to;
+// otherwise it may match at any depth.
+fn anchored(pattern: &str) -> Result<Glob, GlobError> {
+    if pattern.contains('/') {
+        Glob::new(pattern.trim_start_matches('/'))
+    } else {
+        Glob::new(&format!("**/{}", pattern))
+    }
+}
+
+/// Which files a directory walk keeps, from `--glob` and `--type`.
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    // Rules whose `negated` means the entry is dropped.
+    globs: Vec<Rule>,
+    types: Vec<Glob>,
+}
+
+impl Filter {
+    pub fn add_glob(&mut self, pattern: &str) -> Result<(), GlobError> {
+        let (negated, pattern) = match pattern.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, pattern),
+        };
+        let (dir_only, pattern) = match pattern.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, pattern),
+        };
+
+        self.globs.push(Rule {
+            glob: anchored(pattern)?,
+            negated,
+            dir_only,
+        });
+        Ok(())
+    }
+
+    pub fn add_type(&mut self, name: &str) -> Result<(), String> {
+        let globs = types::globs(name)
+            .ok_or_else(|| format!("unknown file type; known types are {}", types::names()))?;
+        for glob in globs {
+            self.types
+                .push(anchored(glob).expect("file type globs are valid"));
+        }
+        Ok(())
+    }
+
+    // Whether the last glob matching `relative` keeps it, if any does.
+    fn overridden(&self, relative: &str, is_dir: bool) -> Option<bool> {
+        self.globs
+            .iter()
+            .rev()
+            .find(|rule| (is_dir || !rule.dir_only) && rule.glob.is_match(relative))
+            .map(|rule| !rule.negated)
+    }
+
+    // Whether a file no glob matched is kept.
+    fn keeps(&self, relative: &str) -> bool {
+        let kept_by_glob = self.globs.iter().any(|rule| !rule.negated);
+        !kept_by_glob
+            && (self.types.is_empty() || self.types.iter().any(|glob| glob.is_match(relative)))
+    }
+}
+
+fn is_ignored(ignores: &[IgnoreDir], path: &Path, is_dir: bool) -> bool {
+    ignores
+        .iter()
+        .rev()
+        .find_map(|dir| dir.matched(path, is_dir))
+        .unwrap_or(false)
+}
+
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with('.'))
+}
+
+/// Expands `paths` into the list of files to search, in a stable order.
+/// Symbolic links met while walking a directory are followed to files but
+/// not to directories, which could lead back up the tree. A path or
+/// directory that cannot be read is passed to `report` and left out, and
+/// the rest are still walked.
+pub fn files<F>(paths: &[String], filter: &Filter, mut report: F) -> Vec<PathBuf>
+where
+    F: FnMut(MinigrepError),
+{
+    let mut files = Vec::new();
+
+    for path in paths {
+        let path = PathBuf::from(path);
+        if path.as_os_str() == crate::STDIN_PATH {
+            files.push(path);
+            continue;
+        }
+        match fs::metadata(&path) {
+            Ok(metadata) if metadata.is_dir() => walk_dir(
+                &path,
+                &path,
+                filter,
+                &mut Vec::new(),
+                &mut files,
+                &mut report,
+            ),
+            Ok(_) => files.push(path),
+            Err(e) => report(MinigrepError::io(&path, e)),
+        }
+    }
+
+    files
+}
+
+fn walk_dir(
+    root: &Path,
+    dir: &Path,
+    filter: &Filter,
+    ignores: &mut Vec<IgnoreDir>,
+    files: &mut Vec<PathBuf>,
+    report: &mut dyn FnMut(MinigrepError),
+) {
+    let entries = fs::read_dir(dir).and_then(|entries| {
+        entries
+            .map(|entry| entry.and_then(|entry| Ok((entry.path(), entry.file_type()?))))
+            .collect::<io::Result<Vec<_>>>()
+    });
+    let mut entries = match entries {
+        Ok(entries) => entries,
+        Err(e) => return report(MinigrepError::io(dir, e)),
+    };
+    entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let loaded = IgnoreDir::load(dir);
+    let pushed = loaded.is_some();
+    ignores.extend(loaded);
+
+    for (path, file_type) in entries {
+        if is_hidden(&path) {
+            continue;
+        }
+        let is_dir = if file_type.is_symlink() {
+            // Dangling links are skipped along with links to directories.
+            match fs::metadata(&path) {
+                Ok(metadata) if !metadata.is_dir() => false,
+                _ => continue,
+            }
+        } else {
+            file_type.is_dir()
+        };
+        let relative = path.strip_prefix(root).unwrap_or(&path);
+        let relative = relative.to_string_lossy().replace('\\', "/");
+        let kept = match filter.overridden(&relative, is_dir) {
+            Some(kept) => kept,
+            None => !is_ignored(ignores, &path, is_dir) && (is_dir || filter.keeps(&relative)),
+        };
+        if !kept {
+            continue;
+        }
+
+        if is_dir {
+            walk_dir(root, &path, filter, ignores, files, report);
+        } else {
+            files.push(path);
+        }
+    }
+
+    if pushed {
+        ignores.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("minigrep-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn relative_files(root: &Path, filter: &Filter) -> Vec<String> {
+        files(&[root.to_string_lossy().into_owned()], filter, |e| {
+            panic!("{}", e)
+        })
+        .iter()
+        .map(|path| {
+            path.strip_prefix(root)
+                .unwrap()
+                .to_string_lossy()
+                .into_owned()
+        })
+        .collect()
+    }
+
+    fn write(root: &Path, path: &str, contents: &str) {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn walks_directories_with_ignore_rules() {
+        let root = scratch_dir("walk");
+        write(&root, ".gitignore", "target/\n*.log\n!keep.log\n/top.txt\n");
+        write(&root, "src/main.rs", "");
+        write(&root, "src/.ignore", "generated.rs\n");
+        write(&root, "src/generated.rs", "");
+        write(&root, "target/debug/out", "");
+        write(&root, "build.log", "");
+        write(&root, "keep.log", "");
+        write(&root, "top.txt", "");
+        write(&root, "docs/top.txt", "");
+        write(&root, ".hidden/file", "");
+
+        let found = relative_files(&root, &Filter::default());
+        assert_eq!(vec!["docs/top.txt", "keep.log", "src/main.rs"], found);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn filters_by_glob_and_type() {
+        let root = scratch_dir("filter");
+        write(&root, ".gitignore", "*.log\n");
+        write(&root, "Cargo.toml", "");
+        write(&root, "Cargo.lock", "");
+        write(&root, "src/main.rs", "");
+        write(&root, "src/lib.rs", "");
+        write(&root, "src/notes.txt", "");
+        write(&root, "target/debug/build.rs", "");
+        write(&root, "debug.log", "");
+
+        let mut filter = Filter::default();
+        filter.add_glob("!target/").unwrap();
+        filter.add_glob("!lib.rs").unwrap();
+        assert_eq!(
+            vec!["Cargo.lock", "Cargo.toml", "src/main.rs", "src/notes.txt"],
+            relative_files(&root, &filter)
+        );
+
+        filter.add_type("rust").unwrap();
+        filter.add_type("toml").unwrap();
+        assert_eq!(
+            vec!["Cargo.toml", "src/main.rs"],
+            relative_files(&root, &filter)
+        );
+
+        // Globs that keep files win over types and ignore files alike.
+        let mut filter = Filter::default();
+        filter.add_glob("*.{toml,log}").unwrap();
+        filter.add_type("rust").unwrap();
+        assert_eq!(
+            vec!["Cargo.toml", "debug.log"],
+            relative_files(&root, &filter)
+        );
+
+        assert!(Filter::default().add_type("cobol").is_err());
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn reports_missing_paths_and_walks_the_rest() {
+        let root = scratch_dir("missing");
+        write(&root, "a.txt", "");
+        let paths = [
+            root.join("missing.txt").to_string_lossy().into_owned(),
+            root.to_string_lossy().into_owned(),
+        ];
+
+        let mut errors = Vec::new();
+        let found = files(&paths, &Filter::default(), |e| errors.push(e.to_string()));
+        assert_eq!(vec![root.join("a.txt")], found);
+        assert_eq!(1, errors.len());
+        assert!(errors[0].contains("missing.txt"), "{}", errors[0]);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn follows_links_to_files_but_not_directories() {
+        use std::os::unix::fs::symlink;
+
+        let root = scratch_dir("links");
+        write(&root, "a/x.txt", "");
+        symlink("..", root.join("a/loop")).unwrap();
+        symlink("a/x.txt", root.join("link.txt")).unwrap();
+        symlink("missing.txt", root.join("dangling.txt")).unwrap();
+
+        assert_eq!(
+            vec!["a/x.txt", "link.txt"],
+            relative_files(&root, &Filter::default())
+        );
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,76 @@
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn prints_lines_from_standard_input_before_it_ends() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_minigrep"))
+        .arg("a")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    let stdout = BufReader::new(child.stdout.take().unwrap());
+
+    let (sender, lines) = mpsc::channel();
+    let reader = thread::spawn(move || {
+        for line in stdout.lines() {
+            sender.send(line.unwrap()).unwrap();
+        }
+    });
+
+    // The pipe is still open, so only a line printed as soon as it was
+    // found can turn up.
+    stdin.write_all(b"a1\nb1\n").unwrap();
+    assert_eq!(
+        Ok(String::from("a1")),
+        lines.recv_timeout(Duration::from_secs(10))
+    );
+
+    stdin.write_all(b"a2\n").unwrap();
+    drop(stdin);
+    assert_eq!(
+        Ok(String::from("a2")),
+        lines.recv_timeout(Duration::from_secs(10))
+    );
+    assert!(child.wait().unwrap().success());
+    reader.join().unwrap();
+}
+
+#[test]
+fn searches_for_the_word_index() {
+    let path = std::env::temp_dir().join(format!("minigrep-cli-index-{}", std::process::id()));
+    std::fs::write(&path, "an index here\nnothing\n").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_minigrep"))
+        .arg("index")
+        .arg(&path)
+        .output()
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(output.status.success());
+    assert_eq!("an index here\n", String::from_utf8_lossy(&output.stdout));
+}
+
+#[test]
+fn searches_the_other_paths_when_one_is_missing() {
+    let dir = std::env::temp_dir().join(format!("minigrep-cli-missing-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("a.txt"), "hello\n").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_minigrep"))
+        .current_dir(&dir)
+        .args(["hello", "a.txt", "missing.txt"])
+        .output()
+        .unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(Some(2), output.status.code());
+    assert_eq!("a.txt:hello\n", String::from_utf8_lossy(&output.stdout));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("missing.txt"), "{}", stderr);
+}